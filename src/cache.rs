@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, time::UNIX_EPOCH};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::probe_media::MediaInfo;
+
+/// Appended to the input file path
+pub const CACHE_EXTENSION: &str = ".ffmpegtrim.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheMode {
+    /// read cached results and store new ones
+    Use,
+    /// ignore cached results and store new ones
+    Refresh,
+    /// neither read nor write cache files
+    Off,
+}
+
+/// Stable 64 bit FNV-1a hash
+pub fn fnv_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Content hash of a reference file, e.g. intro frame image
+pub fn file_hash(filepath: &str) -> String {
+    let data = fs::read(filepath).unwrap_or_else(|_| panic!("Unable to read {filepath}"));
+
+    format!("{:016x}", fnv_hash(&data))
+}
+
+fn detection_key(kind: &str, parameters: &[&str]) -> String {
+    format!("{kind}:{:016x}", fnv_hash(parameters.join("\0").as_bytes()))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheEntry {
+    path: String,
+    size: u64,
+    /// modification time, seconds since epoch
+    modified: u64,
+    media_info: Option<MediaInfo>,
+    /// `<kind>:<parameters hash>` to stored result
+    detections: BTreeMap<String, serde_json::Value>,
+}
+
+/// Probe and detection results of one input file kept in a sidecar `.ffmpegtrim.json` file.
+/// Results are dropped when file size or modification time changes.
+pub struct FileCache {
+    cache_filepath: PathBuf,
+    entry: CacheEntry,
+    mode: CacheMode,
+}
+
+impl FileCache {
+    pub fn open(input_filepath: &str, mode: CacheMode) -> FileCache {
+        let metadata = fs::metadata(input_filepath).expect("Unable to read input file metadata");
+        let fresh = CacheEntry {
+            path: input_filepath.to_owned(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |modified| modified.as_secs()),
+            ..Default::default()
+        };
+        let cache_filepath = PathBuf::from(format!("{input_filepath}{CACHE_EXTENSION}"));
+
+        let entry = match mode {
+            CacheMode::Use => fs::read_to_string(&cache_filepath)
+                .ok()
+                .and_then(|content| serde_json::from_str::<CacheEntry>(&content).ok())
+                .filter(|entry| {
+                    entry.path == fresh.path
+                        && entry.size == fresh.size
+                        && entry.modified == fresh.modified
+                })
+                .unwrap_or(fresh),
+            CacheMode::Refresh | CacheMode::Off => fresh,
+        };
+
+        FileCache {
+            cache_filepath,
+            entry,
+            mode,
+        }
+    }
+
+    pub fn media_info(&mut self, probe: impl FnOnce() -> MediaInfo) -> MediaInfo {
+        if let Some(media_info) = &self.entry.media_info {
+            println!("Using cached media info");
+            return media_info.clone();
+        }
+        let media_info = probe();
+        self.entry.media_info = Some(media_info.clone());
+        self.save();
+
+        media_info
+    }
+
+    /// Cached result of `kind` detection for `parameters` (including reference file hashes),
+    /// `detect` runs when there is none.
+    pub fn detection<T: Serialize + DeserializeOwned>(
+        &mut self,
+        kind: &str,
+        parameters: &[&str],
+        detect: impl FnOnce() -> T,
+    ) -> T {
+        if let Some(result) = self.cached(kind, parameters) {
+            return result;
+        }
+        let result = detect();
+        self.store(kind, parameters, &result);
+
+        result
+    }
+
+    /// Cached result of `kind` detection for `parameters`, if any.
+    pub fn cached<T: DeserializeOwned>(&self, kind: &str, parameters: &[&str]) -> Option<T> {
+        let result = self
+            .entry
+            .detections
+            .get(&detection_key(kind, parameters))
+            .and_then(|value| serde_json::from_value(value.clone()).ok())?;
+        println!("Using cached {kind} detection");
+
+        Some(result)
+    }
+
+    /// Stores result of `kind` detection, e.g. one of several detected in a single pass.
+    pub fn store<T: Serialize>(&mut self, kind: &str, parameters: &[&str], result: &T) {
+        let value = serde_json::to_value(result).expect("Unable to serialize detection result");
+        self.entry
+            .detections
+            .insert(detection_key(kind, parameters), value);
+        self.save();
+    }
+
+    /// Writes the sidecar file right away so that results survive a failure later on.
+    fn save(&self) {
+        if self.mode == CacheMode::Off {
+            return;
+        }
+        let content = serde_json::to_string_pretty(&self.entry).expect("Unable to serialize cache");
+        if let Err(e) = fs::write(&self.cache_filepath, content) {
+            println!("Unable to write cache {:?}: {}", self.cache_filepath, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::{fnv_hash, CacheMode, FileCache};
+
+    fn input_file(name: &str, content: &str) -> String {
+        let filepath = env::temp_dir().join(name);
+        fs::write(&filepath, content).unwrap();
+        filepath.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn fnv_hash_is_stable() {
+        assert_eq!(fnv_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv_hash(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn detection_is_cached_per_parameters() {
+        let input_filepath = input_file(
+            &format!("ffmpegtrim_cache_test_{}.mkv", process::id()),
+            "video",
+        );
+        let mut cache = FileCache::open(&input_filepath, CacheMode::Refresh);
+        assert_eq!(
+            cache.detection("frame", &["hash", "2"], || vec![1.0_f32]),
+            vec![1.0]
+        );
+
+        let mut cache = FileCache::open(&input_filepath, CacheMode::Use);
+        assert_eq!(
+            cache.detection("frame", &["hash", "2"], || vec![2.0_f32]),
+            vec![1.0]
+        );
+        assert_eq!(
+            cache.detection("frame", &["hash", "4"], || vec![4.0_f32]),
+            vec![4.0]
+        );
+
+        // changed input drops cached results
+        fs::write(&input_filepath, "other video").unwrap();
+        let mut cache = FileCache::open(&input_filepath, CacheMode::Use);
+        assert_eq!(
+            cache.detection("frame", &["hash", "2"], || vec![3.0_f32]),
+            vec![3.0]
+        );
+        fs::remove_file(format!("{input_filepath}.ffmpegtrim.json")).ok();
+        fs::remove_file(&input_filepath).ok();
+    }
+}
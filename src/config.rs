@@ -0,0 +1,102 @@
+use std::{fs, path::Path};
+
+pub const CONFIG_FILENAME: &str = "ffmpegtrim.ini";
+
+/// Turns config lines into command line arguments.
+/// Keys before the first `[section]` apply to every show, keys inside `[show]` only to that show.
+///
+/// ```ini
+/// ext = mkv
+///
+/// [myshow]
+/// if = intro.jpg
+/// intro-offset = -0.5
+/// se = true
+/// ```
+pub fn config_args(content: &str, show: &str) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    let mut section = String::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_owned();
+            continue;
+        }
+        if !section.is_empty() && section != show {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (line, "true"),
+        };
+        let flag = match key.len() == 1 {
+            true => format!("-{key}"),
+            false => format!("--{key}"),
+        };
+        match value {
+            "true" => args.push(flag),
+            "false" => {}
+            _ => {
+                // keep negative values such as offsets attached to the flag
+                args.push(format!("{flag}={value}"));
+            }
+        }
+    }
+
+    args
+}
+
+/// Reads `config_filepath` (or `ffmpegtrim.ini` inside `dir`) when it exists.
+pub fn read_config_args(config_filepath: &str, dir: &str, show: &str) -> Vec<String> {
+    let path = match config_filepath.is_empty() {
+        true => Path::new(dir).join(CONFIG_FILENAME),
+        false => Path::new(config_filepath).to_path_buf(),
+    };
+    if !path.is_file() {
+        if !config_filepath.is_empty() {
+            panic!("Config file {config_filepath} does not exist!");
+        }
+        return Vec::new();
+    }
+
+    let content = fs::read_to_string(&path).expect("Unable to read config file");
+    println!("Using config {:?} (show {:?})", path, show);
+
+    config_args(&content, show)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::config_args;
+
+    #[test]
+    fn config_args_for_nothing() {
+        assert!(config_args("", "").is_empty());
+        assert!(config_args("# comment\n\n", "show").is_empty());
+    }
+
+    #[test]
+    fn config_args_global_and_show() {
+        let content =
+            "ext = mkv\n[a]\nif = a.jpg\nintro-offset = -0.5\nse = true\n[b]\nif = b.jpg\n";
+
+        assert_eq!(
+            config_args(content, "a"),
+            vec!["--ext=mkv", "--if=a.jpg", "--intro-offset=-0.5", "--se"]
+        );
+        assert_eq!(config_args(content, "b"), vec!["--ext=mkv", "--if=b.jpg"]);
+        assert_eq!(config_args(content, ""), vec!["--ext=mkv"]);
+    }
+
+    #[test]
+    fn config_args_short_and_disabled_flags() {
+        assert_eq!(
+            config_args("x = mkv\nse = false\nl", ""),
+            vec!["-x=mkv", "-l"]
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+
+use crate::{
+    helpers::parse_time,
+    match_interval::MatchInterval,
+    snap::{snap_nearest, Snapper},
+};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OnNoMatch {
+    /// leave the file untouched
+    Skip,
+    /// use --ts/--te instead
+    Fallback,
+    /// stop processing with an error
+    Fail,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AfterOutro {
+    /// remove the outro only, e.g. keep next episode preview (needs an audio or credits match)
+    Keep,
+    /// trim everything from the outro start
+    Drop,
+}
+
+pub enum CutSource {
+    /// `detector` matched, `time` already includes `offset`
+    Detected {
+        detector: &'static str,
+        time: f32,
+        offset: f32,
+        interval: MatchInterval,
+    },
+    /// value of a command line option, e.g. --ts
+    Manual(&'static str),
+    Nothing,
+}
+
+pub struct Snapped {
+    /// trim value before snapping
+    pub raw_trim: String,
+    pub snapper: &'static str,
+}
+
+pub struct Cut {
+    /// value passed to trim_start_end (seconds from start or from end of file)
+    pub trim: String,
+    pub source: CutSource,
+    pub snapped: Option<Snapped>,
+    /// detected boundary (seconds from start) when the file is trimmed elsewhere,
+    /// e.g. from a cold open before the intro
+    pub boundary: Option<f32>,
+}
+
+impl Cut {
+    pub fn detected(
+        detector: &'static str,
+        trim: f32,
+        time: f32,
+        offset: f32,
+        interval: MatchInterval,
+    ) -> Cut {
+        Cut {
+            trim: trim.to_string(),
+            source: CutSource::Detected {
+                detector,
+                time,
+                offset,
+                interval,
+            },
+            snapped: None,
+            boundary: None,
+        }
+    }
+
+    pub fn manual(trim: &str, option: &'static str) -> Cut {
+        Cut {
+            trim: trim.to_owned(),
+            source: match trim.is_empty() {
+                true => CutSource::Nothing,
+                false => CutSource::Manual(option),
+            },
+            snapped: None,
+            boundary: None,
+        }
+    }
+
+    /// Moves the cut to the nearest snap point, `from_end` for trim end values.
+    /// Values with duration (e.g. `48dur5`) are left as they are.
+    pub fn snap(&mut self, duration: f32, from_end: bool, snappers: &[Snapper]) {
+        let Some(trim) = parse_time(&self.trim) else {
+            return;
+        };
+        let time = match from_end {
+            true => duration - trim,
+            false => trim,
+        };
+        if let Some((snapped_time, snapper)) = snap_nearest(time, snappers) {
+            let snapped_trim = match from_end {
+                true => duration - snapped_time,
+                false => snapped_time,
+            };
+            self.snapped = Some(Snapped {
+                raw_trim: std::mem::replace(&mut self.trim, snapped_trim.to_string()),
+                snapper,
+            });
+        }
+    }
+
+    /// Outro start (cut time from start of file) and end of the matched interval, `from_end` cut only.
+    /// Only audio and credits intervals last till the outro end, a frame match ends with the still frame.
+    pub fn outro_segment(&self, duration: f32) -> Option<(f32, f32)> {
+        let CutSource::Detected { interval, .. } = self.source else {
+            return None;
+        };
+        let start = self.detected_time(duration, true)?;
+
+        (interval.end > start).then_some((start, interval.end.min(duration)))
+    }
+
+    /// Trims at `trim` instead, the detected boundary is still reported by `detected_time`.
+    pub fn retrim(&mut self, duration: f32, from_end: bool, trim: f32) {
+        if self.boundary.is_none() {
+            self.boundary = self.detected_time(duration, from_end);
+        }
+        self.trim = trim.to_string();
+    }
+
+    /// Cut time from start of file when it comes from a detector, `from_end` for trim end values.
+    pub fn detected_time(&self, duration: f32, from_end: bool) -> Option<f32> {
+        let CutSource::Detected { .. } = self.source else {
+            return None;
+        };
+        if self.boundary.is_some() {
+            return self.boundary;
+        }
+        let trim = parse_time(&self.trim)?;
+
+        match from_end {
+            true => Some(duration - trim),
+            false => Some(trim),
+        }
+    }
+}
+
+impl fmt::Display for Cut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            CutSource::Detected {
+                detector,
+                time,
+                offset,
+                interval,
+            } => write!(
+                f,
+                "{} from {} match at {} (interval {}-{}, offset {}, score {}, {} frames)",
+                self.trim,
+                detector,
+                time,
+                interval.start,
+                interval.end,
+                offset,
+                interval.peak,
+                interval.frames
+            ),
+            CutSource::Manual(option) => write!(f, "{} from {}", self.trim, option),
+            CutSource::Nothing => write!(f, "none"),
+        }?;
+        if let Some(boundary) = self.boundary {
+            write!(f, " trimmed before boundary {}", boundary)?;
+        }
+        if let Some(snapped) = &self.snapped {
+            write!(
+                f,
+                " snapped to {} (raw {})",
+                snapped.snapper, snapped.raw_trim
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies the no-match policy, returns true when the manual value may be used instead.
+pub fn allow_fallback(on_no_match: OnNoMatch, what: &str, input_filename: &str) -> bool {
+    match on_no_match {
+        OnNoMatch::Skip => {
+            println!("No {what} matched in {input_filename}, skipping file");
+            false
+        }
+        OnNoMatch::Fallback => true,
+        OnNoMatch::Fail => panic!("No {what} matched in {input_filename}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{allow_fallback, Cut, OnNoMatch};
+    use crate::{match_interval::MatchInterval, snap::Snapper};
+
+    #[test]
+    fn display_detected_cut() {
+        let interval = MatchInterval {
+            start: 60.0,
+            end: 64.0,
+            peak: 98.0,
+            frames: 5,
+        };
+        let cut = Cut::detected("frame", 64.5, 64.5, 0.5, interval);
+
+        assert_eq!(
+            cut.to_string(),
+            "64.5 from frame match at 64.5 (interval 60-64, offset 0.5, score 98, 5 frames)"
+        );
+    }
+
+    #[test]
+    fn detected_time_of_cuts() {
+        let interval = MatchInterval {
+            start: 1200.0,
+            end: 1210.0,
+            peak: 98.0,
+            frames: 11,
+        };
+
+        assert_eq!(
+            Cut::detected("frame", 100.0, 1200.0, 0.0, interval).detected_time(1300.0, true),
+            Some(1200.0)
+        );
+        assert_eq!(Cut::manual("45", "--ts").detected_time(1300.0, false), None);
+    }
+
+    #[test]
+    fn retrim_keeps_detected_time() {
+        // intro 30 - 90 after a cold open, only the intro is removed
+        let interval = MatchInterval {
+            start: 30.0,
+            end: 90.0,
+            peak: 0.0,
+            frames: 0,
+        };
+        let mut cut = Cut::detected("discover", 90.0, 90.0, 0.0, interval);
+        cut.retrim(1300.0, false, 0.0);
+
+        assert_eq!(cut.trim, "0");
+        assert_eq!(cut.detected_time(1300.0, false), Some(90.0));
+        assert_eq!(
+            cut.to_string(),
+            "0 from discover match at 90 (interval 30-90, offset 0, score 0, 0 frames) trimmed before boundary 90"
+        );
+    }
+
+    #[test]
+    fn outro_segment_till_interval_end() {
+        let interval = MatchInterval {
+            start: 1200.0,
+            end: 1290.0,
+            peak: 98.0,
+            frames: 91,
+        };
+
+        // outro offset 1 second
+        assert_eq!(
+            Cut::detected("audio", 99.0, 1201.0, 1.0, interval).outro_segment(1300.0),
+            Some((1201.0, 1290.0))
+        );
+        assert_eq!(Cut::manual("20", "--te").outro_segment(1300.0), None);
+    }
+
+    #[test]
+    fn display_manual_cut() {
+        assert_eq!(Cut::manual("45", "--ts").to_string(), "45 from --ts");
+        assert_eq!(Cut::manual("", "--te").to_string(), "none");
+    }
+
+    #[test]
+    fn snap_manual_cuts() {
+        let silence = [MatchInterval {
+            start: 44.0,
+            end: 44.5,
+            peak: 0.5,
+            frames: 0,
+        }];
+        let snappers = [Snapper {
+            name: "silence",
+            intervals: &silence,
+            radius: 1.0,
+        }];
+
+        let mut start = Cut::manual("45", "--ts");
+        start.snap(100.0, false, &snappers);
+        assert_eq!(start.trim, "44.5");
+        assert_eq!(
+            start.to_string(),
+            "44.5 from --ts snapped to silence (raw 45)"
+        );
+
+        let mut end = Cut::manual("55.5", "--te");
+        end.snap(100.0, true, &snappers);
+        assert_eq!(end.trim, "55.5");
+
+        let mut with_duration = Cut::manual("45dur5", "--ts");
+        with_duration.snap(100.0, false, &snappers);
+        assert_eq!(with_duration.trim, "45dur5");
+    }
+
+    #[test]
+    fn allow_fallback_policies() {
+        assert!(allow_fallback(OnNoMatch::Fallback, "intro", "a.mp4"));
+        assert!(!allow_fallback(OnNoMatch::Skip, "intro", "a.mp4"));
+    }
+
+    #[test]
+    #[should_panic(expected = "No outro matched in a.mp4")]
+    fn allow_fallback_fail() {
+        allow_fallback(OnNoMatch::Fail, "outro", "a.mp4");
+    }
+}
@@ -0,0 +1,61 @@
+use ffmpeg_sidecar::{command::FfmpegCommand, event::FfmpegEvent};
+
+/// Decodes the input as downscaled grayscale frames sampled at `sample_rate` Hz.
+/// `on_frame` receives the frame time in the input (seconds) and `width * height` luma bytes.
+pub fn decode_gray_frames(
+    input_filepath: &str,
+    sample_rate: f32,
+    width: u32,
+    height: u32,
+    seek: Option<(f32, f32)>,
+    mut on_frame: impl FnMut(f32, &[u8]),
+) {
+    let mut command = FfmpegCommand::new();
+    let mut start: f32 = 0.0;
+    if let Some((seek_start, seek_duration)) = seek {
+        start = seek_start.max(0.0);
+        command.args(["-ss", &start.to_string(), "-t", &seek_duration.to_string()]);
+    }
+    command
+        .args([
+            "-i",
+            input_filepath,
+            "-an", // no audio
+            "-vf",
+            format!(
+                "fps={},scale={}:{}:flags=area,format=gray",
+                sample_rate, width, height
+            )
+            .as_str(),
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "gray",
+        ])
+        .pipe_stdout()
+        .spawn()
+        .expect("Unable to spawn child process")
+        .iter()
+        .expect("Unable to obtain child process iterator")
+        .for_each(|e| match e {
+            FfmpegEvent::Error(err) => println!("Error decoding frames\n{}", err),
+            // timestamp is frame_num / fps, the same for every run
+            FfmpegEvent::OutputFrame(frame) => on_frame(start + frame.timestamp, &frame.data),
+            _ => {}
+        });
+}
+
+/// First frame of a still image (or video) in the same format as `decode_gray_frames`.
+pub fn decode_gray_image(image_filepath: &str, width: u32, height: u32) -> Vec<u8> {
+    let mut image: Vec<u8> = Vec::new();
+    decode_gray_frames(image_filepath, 1.0, width, height, None, |_, data| {
+        if image.is_empty() {
+            image = data.to_vec();
+        }
+    });
+    if image.is_empty() {
+        panic!("Unable to decode image {image_filepath}");
+    }
+
+    image
+}
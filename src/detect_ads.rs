@@ -0,0 +1,112 @@
+use crate::match_interval::MatchInterval;
+
+/// Commercial spots inside one break are rarely further apart than this (in seconds)
+const MAX_SPOT_SECONDS: f32 = 120.0;
+
+/// Moments where black frames and silence overlap, likely borders between programme and ads.
+pub fn break_markers(black: &[MatchInterval], silence: &[MatchInterval]) -> Vec<MatchInterval> {
+    let mut markers: Vec<MatchInterval> = black
+        .iter()
+        .flat_map(|black| {
+            silence.iter().filter_map(move |silence| {
+                let start = black.start.max(silence.start);
+                let end = black.end.min(silence.end);
+                match end >= start {
+                    true => Some(MatchInterval {
+                        start,
+                        end,
+                        peak: end - start,
+                        frames: 0,
+                    }),
+                    false => None,
+                }
+            })
+        })
+        .collect();
+    markers.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    markers
+}
+
+/// Groups markers no further than a commercial spot apart into ad blocks,
+/// only blocks between `min_block` and `max_block` seconds long are kept.
+pub fn group_ad_blocks(
+    markers: &[MatchInterval],
+    min_block: f32,
+    max_block: f32,
+) -> Vec<MatchInterval> {
+    let mut chains: Vec<MatchInterval> = Vec::new();
+    for marker in markers {
+        match chains.last_mut() {
+            Some(chain) if marker.start - chain.end <= MAX_SPOT_SECONDS => {
+                chain.end = marker.end;
+                chain.frames += 1;
+            }
+            _ => chains.push(MatchInterval {
+                start: marker.start,
+                end: marker.end,
+                peak: 0.0,
+                frames: 1,
+            }),
+        }
+    }
+
+    chains
+        .into_iter()
+        .filter(|chain| chain.frames > 1)
+        .map(|chain| MatchInterval {
+            peak: chain.end - chain.start,
+            ..chain
+        })
+        .filter(|block| block.peak >= min_block && block.peak <= max_block)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{break_markers, group_ad_blocks};
+    use crate::match_interval::MatchInterval;
+
+    #[test]
+    fn break_markers_from_overlaps() {
+        let black = [
+            MatchInterval::new(10.0, 11.0),
+            MatchInterval::new(50.0, 51.0),
+            MatchInterval::new(70.0, 71.0),
+        ];
+        let silence = [
+            MatchInterval::new(10.5, 12.0),
+            MatchInterval::new(69.0, 72.0),
+        ];
+
+        assert_eq!(
+            break_markers(&black, &silence),
+            vec![
+                MatchInterval::new(10.5, 11.0),
+                MatchInterval::new(70.0, 71.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn group_ad_blocks_by_length() {
+        let markers = [
+            MatchInterval::new(100.0, 100.5),
+            MatchInterval::new(130.0, 130.5),
+            MatchInterval::new(160.0, 160.5),
+            MatchInterval::new(220.0, 220.5),
+            // lonely marker, a scene transition
+            MatchInterval::new(900.0, 900.5),
+            // too short block
+            MatchInterval::new(1500.0, 1500.5),
+            MatchInterval::new(1510.0, 1510.5),
+        ];
+
+        let blocks = group_ad_blocks(&markers, 60.0, 600.0);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].start, 100.0);
+        assert_eq!(blocks[0].end, 220.5);
+        assert_eq!(blocks[0].frames, 4);
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    io::{BufReader, Read},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    fingerprint::{find_matches, fingerprint, SAMPLE_RATE},
+    match_interval::MatchInterval,
+};
+
+/// Decodes the first audio stream into mono signed 16 bit samples,
+/// only the first `duration_limit` seconds when given.
+pub fn decode_audio(filepath: &str, duration_limit: Option<f32>) -> Vec<i16> {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", "error"]);
+    if let Some(duration_limit) = duration_limit {
+        command.args(["-t", &duration_limit.to_string()]);
+    }
+    let mut child = command
+        .arg("-i")
+        .arg(filepath)
+        .args([
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            &SAMPLE_RATE.to_string(),
+            "-f",
+            "s16le",
+            "-",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stdout = child.stdout.take().expect("No child stdout");
+
+    let mut reader = BufReader::new(stdout);
+
+    let mut bytes: Vec<u8> = Vec::new();
+
+    reader.read_to_end(&mut bytes).unwrap();
+
+    let status = child.wait().unwrap();
+
+    if !status.success() {
+        panic!("Unable to decode {filepath} audio!");
+    }
+
+    bytes
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+pub struct AudioReference<'a> {
+    pub clip_filepath: &'a str,
+    pub greater_than_duration: &'a str,
+    pub less_than_duration: &'a str,
+}
+
+/// Decodes the input audio once and finds where each reference clip (e.g. theme song) plays.
+pub fn detect_audio(
+    input_filepath: &str,
+    references: &[AudioReference],
+    max_bit_error_rate: &str,
+) -> Vec<Vec<MatchInterval>> {
+    let max_bit_error_rate: f32 = max_bit_error_rate
+        .parse()
+        .expect("Unable to parse audio match bit error rate");
+    println!("Audio analyse started... =>");
+
+    let haystack = fingerprint(&decode_audio(input_filepath, None));
+
+    references
+        .iter()
+        .map(|reference| {
+            let greater_than_duration: f32 =
+                reference.greater_than_duration.parse().unwrap_or(-1.0);
+            let lower_than_duration: f32 = reference.less_than_duration.parse().unwrap_or(-1.0);
+            let needle = fingerprint(&decode_audio(reference.clip_filepath, None));
+
+            find_matches(
+                &haystack,
+                &needle,
+                max_bit_error_rate,
+                greater_than_duration,
+                lower_than_duration,
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,146 @@
+use ffmpeg_sidecar::{
+    command::FfmpegCommand,
+    event::{FfmpegEvent, LogLevel},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{helpers::parse_log_value, match_interval::MatchInterval};
+
+#[derive(Serialize, Deserialize)]
+pub struct BlackScenes {
+    /// black segments, the interval peak is the segment duration
+    pub black: Vec<MatchInterval>,
+    /// hard scene changes (start equals end), the interval peak is the scdet score
+    pub scenes: Vec<MatchInterval>,
+}
+
+/// Parses a blackdetect log line into a black segment.
+pub fn parse_black_line(msg: &str) -> Option<MatchInterval> {
+    if !msg.contains("[blackdetect @") {
+        return None;
+    }
+    // e.g. [blackdetect @ 0x55d0c4b1e2c0] [info] black_start:0 black_end:1.52 black_duration:1.52
+    let start = parse_log_value(msg, "black_start:")?;
+    let end = parse_log_value(msg, "black_end:")?;
+
+    Some(MatchInterval {
+        start,
+        end,
+        peak: end - start,
+        frames: 0,
+    })
+}
+
+/// Parses a scdet log line into a scene change.
+pub fn parse_scene_line(msg: &str) -> Option<MatchInterval> {
+    if !msg.contains("[scdet @") {
+        return None;
+    }
+    // e.g. [scdet @ 0x55d0c4b1f400] [info] lavfi.scd.score: 59.252, lavfi.scd.time: 23.8
+    let score = parse_log_value(msg, "lavfi.scd.score:")?;
+    let time = parse_log_value(msg, "lavfi.scd.time:")?;
+
+    Some(MatchInterval {
+        start: time,
+        end: time,
+        peak: score,
+        frames: 1,
+    })
+}
+
+/// Runs blackdetect and, when `scene_threshold` is given, scdet over the input in one pass.
+pub fn detect_black_scenes(
+    input_filepath: &str,
+    black_duration: &str,
+    black_threshold: &str,
+    scene_threshold: Option<&str>,
+) -> BlackScenes {
+    let mut filter = format!(
+        "blackdetect=d={}:pix_th={}",
+        black_duration, black_threshold
+    );
+    if let Some(scene_threshold) = scene_threshold {
+        filter.push_str(&format!(",scdet=threshold={}", scene_threshold));
+    }
+    println!("Black/scene analyse started... ({filter}) =>");
+
+    let mut black_scenes = BlackScenes {
+        black: Vec::new(),
+        scenes: Vec::new(),
+    };
+
+    FfmpegCommand::new()
+        .args([
+            "-i",
+            input_filepath,
+            "-an", // no audio
+            "-vf",
+            filter.as_str(),
+            "-f",
+            "null",
+            "-",
+        ])
+        .print_command()
+        .spawn()
+        .expect("Unable to spawn child process")
+        .iter()
+        .expect("Unable to obtain child process iterator")
+        .for_each(|e| match e {
+            FfmpegEvent::Log(LogLevel::Error, e) => println!("Error: {}", e),
+            FfmpegEvent::Log(LogLevel::Info, msg) => {
+                if let Some(black) = parse_black_line(&msg) {
+                    black_scenes.black.push(black);
+                } else if let Some(scene) = parse_scene_line(&msg) {
+                    black_scenes.scenes.push(scene);
+                }
+            }
+            _ => {}
+        });
+
+    black_scenes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_black_line, parse_scene_line};
+    use crate::match_interval::MatchInterval;
+
+    #[test]
+    fn parse_black_line_segment() {
+        assert_eq!(
+            parse_black_line(
+                "[blackdetect @ 0x55d0c4b1e2c0] [info] black_start:10.5 black_end:12 black_duration:1.5"
+            ),
+            Some(MatchInterval {
+                start: 10.5,
+                end: 12.0,
+                peak: 1.5,
+                frames: 0
+            })
+        );
+        assert_eq!(
+            parse_black_line("[scdet @ 0x55d0c4b1f400] [info] black_start:10.5 black_end:12"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_scene_line_change() {
+        assert_eq!(
+            parse_scene_line(
+                "[scdet @ 0x55d0c4b1f400] [info] lavfi.scd.score: 59.252, lavfi.scd.time: 23.8"
+            ),
+            Some(MatchInterval {
+                start: 23.8,
+                end: 23.8,
+                peak: 59.252,
+                frames: 1
+            })
+        );
+        assert_eq!(
+            parse_scene_line("[scdet @ 0x55d0c4b1f400] [info] lavfi.scd.score: 59.252"),
+            None
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    io::{BufReader, Read},
+    process::{Command, Stdio},
+};
+
+use crate::match_interval::MatchInterval;
+
+/// Parses `ffprobe -show_entries chapter=start_time,end_time:chapter_tags=title -of csv=p=0` output
+/// keeping chapters whose title contains one of `titles` (case insensitive).
+pub fn parse_chapters(output: &str, titles: &[&str]) -> Vec<MatchInterval> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().splitn(3, ',');
+            let start: f32 = parts.next()?.parse().ok()?;
+            let end: f32 = parts.next()?.parse().ok()?;
+            let title = parts.next().unwrap_or("").to_lowercase();
+            match titles.is_empty() || titles.iter().any(|t| title.contains(&t.to_lowercase())) {
+                true => Some(MatchInterval {
+                    start,
+                    end,
+                    peak: 100.0,
+                    frames: 0,
+                }),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+pub fn detect_chapters(input_filepath: &str, titles: &[&str]) -> Vec<MatchInterval> {
+    let mut child = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "chapter=start_time,end_time:chapter_tags=title",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(input_filepath)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stdout = child.stdout.take().expect("No child stdout");
+
+    let mut reader = BufReader::new(stdout);
+
+    let mut result = String::new();
+
+    reader.read_to_string(&mut result).unwrap();
+
+    let status = child.wait().unwrap();
+
+    if !status.success() {
+        panic!("Unable to read {input_filepath} chapters! Result is {result}");
+    }
+
+    parse_chapters(&result, titles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_chapters;
+
+    #[test]
+    fn parse_chapters_by_title() {
+        let output = "0.000000,95.500000,Opening\n95.500000,1300.000000,Part A\n1300.000000,1390.000000,Ending, credits\n";
+
+        let chapters = parse_chapters(output, &["opening", "ending"]);
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].end, 95.5);
+        assert_eq!(chapters[1].start, 1300.0);
+
+        assert_eq!(parse_chapters(output, &[]).len(), 3);
+        assert!(parse_chapters("", &["opening"]).is_empty());
+    }
+}
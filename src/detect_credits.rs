@@ -0,0 +1,273 @@
+use crate::{decode_frames::decode_gray_frames, match_interval::MatchInterval};
+
+/// Frames are sampled at this rate (Hz)
+const SAMPLE_RATE: f32 = 2.0;
+/// Decoded frame size, small text still leaves sharp edges
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 90;
+/// Credits are searched in this many last seconds of the file
+const SEARCH_SECONDS: f32 = 600.0;
+/// Pixels darker than this count as background
+const MAX_DARK_LUMA: u8 = 60;
+/// Share of dark pixels in a credits frame
+const MIN_DARK_FRACTION: f32 = 0.7;
+/// Horizontal luma step above this is a text edge
+const MIN_EDGE_STEP: u8 = 40;
+/// Share of edge pixels: no text below, busy picture above
+const MIN_EDGE_FRACTION: f32 = 0.01;
+const MAX_EDGE_FRACTION: f32 = 0.3;
+/// Largest upward scroll between two samples (rows)
+const MAX_SHIFT: usize = 15;
+/// Shifted frame must differ at most this part of the unshifted difference
+const MAX_SHIFT_DIFF_RATIO: f32 = 0.6;
+/// Mean difference below this is a still picture
+const MIN_MOTION_DIFF: f32 = 1.0;
+/// Consecutive samples scrolling at a similar speed, a single shift is a camera move
+const MIN_SCROLL_SAMPLES: usize = 3;
+/// Scroll speed difference (rows) still counted as similar
+const MAX_SHIFT_VARIATION: usize = 2;
+/// Credits frames closer than this (in seconds) are chained together
+const CREDITS_GAP: f32 = 3.0;
+/// Shorter runs are title cards or dark scenes
+const MIN_CREDITS_SECONDS: f32 = 20.0;
+
+/// Share of dark pixels
+pub fn dark_fraction(frame: &[u8]) -> f32 {
+    frame.iter().filter(|&&luma| luma < MAX_DARK_LUMA).count() as f32 / frame.len() as f32
+}
+
+/// Share of pixels with a sharp horizontal luma step, high for text
+pub fn edge_fraction(frame: &[u8], width: usize) -> f32 {
+    let edges = frame
+        .chunks(width)
+        .flat_map(|row| row.windows(2))
+        .filter(|pair| pair[0].abs_diff(pair[1]) > MIN_EDGE_STEP)
+        .count();
+
+    edges as f32 / frame.len() as f32
+}
+
+/// Mean difference between `current` rows and `previous` rows `shift` lower.
+fn shifted_difference(previous: &[u8], current: &[u8], width: usize, shift: usize) -> f32 {
+    let rows = previous.len() / width - shift;
+    let sum: u32 = current[..rows * width]
+        .iter()
+        .zip(&previous[shift * width..])
+        .map(|(a, b)| a.abs_diff(*b) as u32)
+        .sum();
+
+    sum as f32 / (rows * width) as f32
+}
+
+/// Rows the picture moved up since `previous`, none when it is still or moves otherwise.
+pub fn vertical_shift(previous: &[u8], current: &[u8], width: usize) -> Option<usize> {
+    let still = shifted_difference(previous, current, width, 0);
+    if still < MIN_MOTION_DIFF {
+        return None;
+    }
+    let max_shift = MAX_SHIFT.min(previous.len() / width / 2);
+    let (shift, difference) = (1..=max_shift)
+        .map(|shift| (shift, shifted_difference(previous, current, width, shift)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    match difference < still * MAX_SHIFT_DIFF_RATIO {
+        true => Some(shift),
+        false => None,
+    }
+}
+
+/// Upward shift of a dark frame with text-like edges, none for other frames
+pub fn credits_shift(previous: &[u8], current: &[u8], width: usize) -> Option<usize> {
+    let edges = edge_fraction(current, width);
+    if dark_fraction(current) < MIN_DARK_FRACTION
+        || !(MIN_EDGE_FRACTION..=MAX_EDGE_FRACTION).contains(&edges)
+    {
+        return None;
+    }
+
+    vertical_shift(previous, current, width)
+}
+
+/// Samples inside `MIN_SCROLL_SAMPLES` consecutive shifts differing at most `MAX_SHIFT_VARIATION` rows.
+pub fn steady_scroll(shifts: &[Option<usize>]) -> Vec<bool> {
+    let mut steady = vec![false; shifts.len()];
+    for (start, window) in shifts.windows(MIN_SCROLL_SAMPLES).enumerate() {
+        let window: Vec<usize> = window.iter().flatten().copied().collect();
+        let (Some(min), Some(max)) = (window.iter().min(), window.iter().max()) else {
+            continue;
+        };
+        if window.len() == MIN_SCROLL_SAMPLES && max - min <= MAX_SHIFT_VARIATION {
+            steady[start..start + MIN_SCROLL_SAMPLES].fill(true);
+        }
+    }
+
+    steady
+}
+
+/// Runs of credits samples lasting at least `MIN_CREDITS_SECONDS`, peak is the share of credits samples.
+pub fn credits_intervals(samples: &[(f32, bool)]) -> Vec<MatchInterval> {
+    let period = 1.0 / SAMPLE_RATE;
+    let mut intervals: Vec<MatchInterval> = Vec::new();
+
+    for &(time, _) in samples.iter().filter(|sample| sample.1) {
+        match intervals.last_mut() {
+            Some(interval) if time <= interval.end + CREDITS_GAP => {
+                interval.end = time + period;
+                interval.frames += 1;
+            }
+            _ => intervals.push(MatchInterval {
+                start: time,
+                end: time + period,
+                peak: 0.0,
+                frames: 1,
+            }),
+        }
+    }
+    intervals.retain(|interval| interval.end - interval.start >= MIN_CREDITS_SECONDS);
+    for interval in intervals.iter_mut() {
+        let samples = (interval.end - interval.start) * SAMPLE_RATE;
+        interval.peak = 100.0 * interval.frames as f32 / samples;
+    }
+
+    intervals
+}
+
+/// Longest credits run, its start is the outro start.
+pub fn credits_start(intervals: &[MatchInterval]) -> Option<MatchInterval> {
+    intervals
+        .iter()
+        .copied()
+        .max_by(|a, b| (a.end - a.start).total_cmp(&(b.end - b.start)))
+}
+
+/// Finds scrolling credits in the last minutes of the input.
+pub fn detect_credits(input_filepath: &str, duration: f32) -> Vec<MatchInterval> {
+    println!("Credits analyse started... (Duration {}) =>", duration);
+
+    let width = WIDTH as usize;
+    let mut shifts: Vec<(f32, Option<usize>)> = Vec::new();
+    let mut previous: Vec<u8> = Vec::new();
+    let seek = (duration - SEARCH_SECONDS, SEARCH_SECONDS);
+    decode_gray_frames(
+        input_filepath,
+        SAMPLE_RATE,
+        WIDTH,
+        HEIGHT,
+        Some(seek),
+        |time, frame| {
+            if previous.len() == frame.len() {
+                shifts.push((time, credits_shift(&previous, frame, width)));
+            }
+            previous = frame.to_vec();
+        },
+    );
+
+    let steady = steady_scroll(&shifts.iter().map(|sample| sample.1).collect::<Vec<_>>());
+    let samples: Vec<(f32, bool)> = shifts
+        .iter()
+        .zip(steady)
+        .map(|(sample, credits)| (sample.0, credits))
+        .collect();
+
+    credits_intervals(&samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        credits_intervals, credits_shift, credits_start, dark_fraction, edge_fraction,
+        steady_scroll, vertical_shift,
+    };
+
+    const WIDTH: usize = 16;
+    const HEIGHT: usize = 32;
+
+    /// Black frame with white text lines, every 8th row starting at `offset`
+    fn credits_frame(offset: usize) -> Vec<u8> {
+        let mut frame = vec![10; WIDTH * HEIGHT];
+        for y in (offset % 8..HEIGHT).step_by(8) {
+            for x in (2..WIDTH - 2).step_by(3) {
+                frame[y * WIDTH + x] = 230;
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn credits_frame_features() {
+        let frame = credits_frame(0);
+        assert!(dark_fraction(&frame) > 0.9);
+        assert!(edge_fraction(&frame, WIDTH) > 0.01);
+        assert_eq!(edge_fraction(&[128; WIDTH * HEIGHT], WIDTH), 0.0);
+    }
+
+    #[test]
+    fn vertical_shift_of_scrolling_text() {
+        // text moves 3 rows up
+        assert_eq!(
+            vertical_shift(&credits_frame(3), &credits_frame(0), WIDTH),
+            Some(3)
+        );
+        // still picture
+        assert_eq!(
+            vertical_shift(&credits_frame(0), &credits_frame(0), WIDTH),
+            None
+        );
+        assert_eq!(
+            credits_shift(&credits_frame(3), &credits_frame(0), WIDTH),
+            Some(3)
+        );
+        assert_eq!(
+            credits_shift(&credits_frame(0), &credits_frame(0), WIDTH),
+            None
+        );
+        assert_eq!(
+            credits_shift(&[200; WIDTH * HEIGHT], &[100; WIDTH * HEIGHT], WIDTH),
+            None
+        );
+    }
+
+    #[test]
+    fn steady_scroll_needs_similar_shifts() {
+        // credits scroll 3-4 rows per sample, then a single dark camera move
+        let shifts = [
+            Some(3),
+            Some(4),
+            Some(3),
+            None,
+            Some(12),
+            None,
+            Some(5),
+            None,
+        ];
+        assert_eq!(
+            steady_scroll(&shifts),
+            [true, true, true, false, false, false, false, false]
+        );
+        // shifts jumping around are no scroll
+        assert_eq!(
+            steady_scroll(&[Some(1), Some(9), Some(2), Some(14)]),
+            [false; 4]
+        );
+    }
+
+    #[test]
+    fn credits_intervals_chained() {
+        // short dark scene at 100, credits from 200 with a 2 seconds gap
+        let samples: Vec<(f32, bool)> = (0..600)
+            .map(|i| i as f32 * 0.5)
+            .map(|time| {
+                let credits = (100.0..105.0).contains(&time)
+                    || ((200.0..299.5).contains(&time) && !(240.0..242.0).contains(&time));
+                (time, credits)
+            })
+            .collect();
+        let intervals = credits_intervals(&samples);
+
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].start, 200.0);
+        assert_eq!(intervals[0].end, 299.5);
+        assert_eq!(credits_start(&intervals).unwrap().start, 200.0);
+        assert!(credits_start(&[]).is_none());
+    }
+}
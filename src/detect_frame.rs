@@ -3,6 +3,13 @@ use ffmpeg_sidecar::{
     event::{FfmpegEvent, LogLevel},
 };
 
+use crate::{
+    decode_frames::{decode_gray_frames, decode_gray_image},
+    helpers::parse_log_value,
+    match_interval::{collect_intervals, MatchInterval, MAX_FRAME_GAP},
+    perceptual_hash::{FrameHash, HASH_BITS, HASH_SIZE},
+};
+
 // let input_filepath_escaped: AsRef<OsStr> = input_filepath.as_ref();
 // let mut command = Command::new("ffmpeg");
 // command.args([
@@ -41,47 +48,223 @@ use ffmpeg_sidecar::{
 // }
 // panic!("Unable to calc duration! Result is {result}");
 
-pub fn detect_frame(
-    input_filepath: &str,
-    frame_filepath: &str,
-    duration: f32,
-    blackframe_amount: &str,
-    blackframe_threshold: &str,
-    greater_than_duration: &str,
-    less_than_duration: &str,
-    first_or_last: bool,
-) -> f32 {
-    let greater_than_duration: f32 = greater_than_duration.parse().unwrap_or(-1.0);
-    let lower_than_duration: f32 = less_than_duration.parse().unwrap_or(-1.0);
-    println!(
-        "Frame analyse started... (Duration {}) gtd ({}) ltd ({})=>",
-        duration, greater_than_duration, lower_than_duration
-    );
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum MatchMetric {
+    /// perceptual hashes (dHash and pHash) of sampled frames
+    Hash,
+    /// structural similarity (0..1) with the looped reference
+    Ssim,
+    /// peak signal to noise ratio (dB) with the looped reference
+    Psnr,
+    /// blend difference with the looped reference scored by blackframe
+    Blackframe,
+}
+
+impl MatchMetric {
+    /// Threshold used when `--ifmt`/`--ofmt` is empty
+    fn default_threshold(&self) -> f32 {
+        match self {
+            MatchMetric::Hash => 16.0,
+            MatchMetric::Ssim => 0.9,
+            MatchMetric::Psnr => 30.0,
+            MatchMetric::Blackframe => 0.0,
+        }
+    }
+
+    /// Frame metadata key holding the score
+    fn metadata_key(&self) -> &'static str {
+        match self {
+            MatchMetric::Ssim => "lavfi.ssim.All=",
+            MatchMetric::Psnr => "lavfi.psnr.psnr_avg=",
+            _ => panic!("{:?} metric has no frame metadata", self),
+        }
+    }
+}
 
-    let mut first_detected_frame_duration: f32 = -1.0;
-    let mut last_detected_frame_duration: f32 = -1.0;
+#[derive(Clone, Copy)]
+pub struct FrameReference<'a> {
+    pub frame_filepath: &'a str,
+    /// maximum hash distance (0..64), minimum ssim (0..1) or minimum psnr (dB) of a match
+    pub match_threshold: &'a str,
+    pub blackframe_amount: &'a str,
+    pub blackframe_threshold: &'a str,
+    pub greater_than_duration: &'a str,
+    pub less_than_duration: &'a str,
+}
 
-    FfmpegCommand::new()
-        .args([
-            "-i",
-            input_filepath,
+pub struct FilterGraph {
+    pub graph: String,
+    /// filter instance names to look for in the log, one per reference
+    pub filter_names: Vec<String>,
+    /// output pads to map, empty for the single reference graph
+    pub outputs: Vec<String>,
+}
+
+/// Builds one filter graph comparing the video against every reference.
+/// The video is split so that each reference gets its own blend/blackframe chain.
+pub fn blackframe_filter_graph(references: &[FrameReference]) -> FilterGraph {
+    if references.len() == 1 {
+        let reference = &references[0];
+        return FilterGraph {
+            graph: format!(
+                "blend=difference:shortest=1,blackframe={}:{}",
+                reference.blackframe_amount, reference.blackframe_threshold
+            ),
+            filter_names: vec!["Parsed_blackframe_1".to_owned()],
+            outputs: vec![],
+        };
+    }
+
+    let split_pads: String = (0..references.len()).map(|i| format!("[v{}]", i)).collect();
+    let mut chains = vec![format!("[0:v]split={}{}", references.len(), split_pads)];
+    let mut filter_names = Vec::new();
+    let mut outputs = Vec::new();
+    for (i, reference) in references.iter().enumerate() {
+        // split is filter 0, every chain adds blend and blackframe
+        chains.push(format!(
+            "[v{}][{}:v]blend=difference:shortest=1,blackframe={}:{}[o{}]",
+            i,
+            i + 1,
+            reference.blackframe_amount,
+            reference.blackframe_threshold,
+            i
+        ));
+        filter_names.push(format!("Parsed_blackframe_{}", 2 + 2 * i));
+        outputs.push(format!("[o{}]", i));
+    }
+
+    FilterGraph {
+        graph: chains.join(";"),
+        filter_names,
+        outputs,
+    }
+}
+
+/// Builds one filter graph scoring sampled frames against every reference with `ssim` or `psnr`.
+/// References are scaled to the video size, scores are printed by `metadata`.
+pub fn metric_filter_graph(
+    references: &[FrameReference],
+    metric: MatchMetric,
+    sample_rate: f32,
+) -> FilterGraph {
+    let filter = match metric {
+        MatchMetric::Ssim => "ssim",
+        MatchMetric::Psnr => "psnr",
+        _ => panic!("{:?} metric has no comparison filter", metric),
+    };
+    let split_pads: String = (0..references.len()).map(|i| format!("[v{}]", i)).collect();
+    let mut chains = vec![format!(
+        "[0:v]fps={},split={}{}",
+        sample_rate,
+        references.len(),
+        split_pads
+    )];
+    let mut filter_names = Vec::new();
+    let mut outputs = Vec::new();
+    for i in 0..references.len() {
+        // fps and split are filters 0 and 1, every chain adds scale2ref, comparison and metadata
+        chains.push(format!(
+            "[{}:v][v{}]scale2ref[r{}][m{}];[m{}][r{}]{}=shortest=1,metadata=mode=print[o{}]",
+            i + 1,
+            i,
+            i,
+            i,
+            i,
+            i,
+            filter,
+            i
+        ));
+        filter_names.push(format!("Parsed_metadata_{}", 4 + 3 * i));
+        outputs.push(format!("[o{}]", i));
+    }
+
+    FilterGraph {
+        graph: chains.join(";"),
+        filter_names,
+        outputs,
+    }
+}
+
+/// Looped reference inputs followed by the filter graph and its outputs.
+fn reference_command(
+    input_filepath: &str,
+    references: &[FrameReference],
+    filter_graph: &FilterGraph,
+) -> FfmpegCommand {
+    let mut command = FfmpegCommand::new();
+    command.args(["-i", input_filepath]);
+    for reference in references {
+        command.args([
             "-r",
             "1", // rate 1 Hz
             "-loop",
             "1", // continue till end
             "-i",
-            frame_filepath,
-            "-an", // no audio
-            "-filter_complex",
-            format!(
-                "blend=difference:shortest=1,blackframe={}:{}",
-                blackframe_amount, blackframe_threshold
-            )
-            .as_str(),
-            "-f",
-            "null",
-            "-",
-        ])
+            reference.frame_filepath,
+        ]);
+    }
+    command.args([
+        "-an", // no audio
+        "-filter_complex",
+        filter_graph.graph.as_str(),
+    ]);
+    for output in &filter_graph.outputs {
+        command.args(["-map", output]);
+    }
+    command.args(["-f", "null", "-"]);
+
+    command
+}
+
+/// Time and ssim/psnr of every sampled frame, for each reference.
+fn metric_scores(
+    input_filepath: &str,
+    references: &[FrameReference],
+    metric: MatchMetric,
+    sample_rate: f32,
+) -> Vec<Vec<(f32, f32)>> {
+    let filter_graph = metric_filter_graph(references, metric, sample_rate);
+    let mut scores: Vec<Vec<(f32, f32)>> = vec![Vec::new(); references.len()];
+    let mut times: Vec<f32> = vec![0.0; references.len()];
+
+    reference_command(input_filepath, references, &filter_graph)
+        .print_command()
+        .spawn()
+        .expect("Unable to spawn child process")
+        .iter()
+        .expect("Unable to obtain child process iterator")
+        .for_each(|e| match e {
+            FfmpegEvent::Log(LogLevel::Error, e) => println!("Error: {}", e),
+            FfmpegEvent::Log(LogLevel::Info, msg) => {
+                let Some(index) = filter_graph
+                    .filter_names
+                    .iter()
+                    .position(|name| msg.contains(&format!("[{} @", name)))
+                else {
+                    return;
+                };
+                // e.g. [Parsed_metadata_4 @ 0x55d0c4b1f400] [info] frame:12   pts:12   pts_time:6
+                if let Some(time) = parse_log_value(&msg, "pts_time:") {
+                    times[index] = time;
+                } else if msg.contains(metric.metadata_key()) {
+                    // e.g. [Parsed_metadata_4 @ 0x55d0c4b1f400] [info] lavfi.ssim.All=0.953012
+                    // identical frames give psnr "inf"
+                    let score = parse_log_value(&msg, metric.metadata_key()).unwrap_or(f32::MAX);
+                    scores[index].push((times[index], score));
+                }
+            }
+            _ => {}
+        });
+
+    scores
+}
+
+/// Time and pblack of every frame blackframe reported, for each reference.
+fn blackframe_scores(input_filepath: &str, references: &[FrameReference]) -> Vec<Vec<(f32, f32)>> {
+    let filter_graph = blackframe_filter_graph(references);
+    let mut hits: Vec<Vec<(f32, f32)>> = vec![Vec::new(); references.len()];
+
+    reference_command(input_filepath, references, &filter_graph)
         .print_command()
         .spawn()
         .expect("Unable to spawn child process")
@@ -91,32 +274,20 @@ pub fn detect_frame(
             FfmpegEvent::Log(LogLevel::Error, e) => println!("Error: {}", e),
             FfmpegEvent::Log(LogLevel::Info, msg) => {
                 // e.g. [ffmpeg] [Parsed_blackframe_1 @ 0x55632ceae440] [info] frame:7711 pblack:92 pts:3948032 t:308.440000 type:B last_keyframe:7700
-                if msg.contains("Parsed_blackframe_1") {
-                    let parts = msg.split_whitespace();
-
-                    for part in parts {
-                        if part.starts_with("t:") {
-                            let t_parts = part.split(":");
-
-                            if let Some(t_part) = t_parts.last() {
-                                // println!("t_part {}", t_part);
-                                let elapsed: f32 = t_part
-                                    .parse()
-                                    .expect(&format!("Unable to parse time value {}", t_part));
-                                if match greater_than_duration != -1.0 {
-                                    true => elapsed > greater_than_duration,
-                                    false => true,
-                                } && match lower_than_duration != -1.0 {
-                                    true => lower_than_duration > elapsed,
-                                    false => true,
-                                } {
-                                    if first_detected_frame_duration == -1.0 {
-                                        first_detected_frame_duration = elapsed;
-                                    } else {
-                                        last_detected_frame_duration = elapsed;
-                                    }
-                                }
-                            }
+                if let Some(index) = filter_graph
+                    .filter_names
+                    .iter()
+                    .position(|name| msg.contains(&format!("[{} @", name)))
+                {
+                    let mut pblack: f32 = 0.0;
+                    for part in msg.split_whitespace() {
+                        if let Some(pblack_part) = part.strip_prefix("pblack:") {
+                            pblack = pblack_part.parse().unwrap_or(0.0);
+                        } else if let Some(t_part) = part.strip_prefix("t:") {
+                            let elapsed: f32 = t_part.parse().unwrap_or_else(|_| {
+                                panic!("Unable to parse time value {}", t_part)
+                            });
+                            hits[index].push((elapsed, pblack));
                         }
                     }
                 } else if msg.starts_with("[info]") {
@@ -135,8 +306,207 @@ pub fn detect_frame(
             _ => {}
         });
 
-    match first_or_last {
-        true => first_detected_frame_duration,
-        false => last_detected_frame_duration,
+    hits
+}
+
+/// Time and hash similarity (0..100) of every sampled frame, for each reference.
+fn hash_scores(
+    input_filepath: &str,
+    references: &[FrameReference],
+    sample_rate: f32,
+) -> Vec<Vec<(f32, f32)>> {
+    let reference_hashes: Vec<FrameHash> = references
+        .iter()
+        .map(|reference| {
+            FrameHash::new(&decode_gray_image(
+                reference.frame_filepath,
+                HASH_SIZE,
+                HASH_SIZE,
+            ))
+        })
+        .collect();
+    let mut scores: Vec<Vec<(f32, f32)>> = vec![Vec::new(); references.len()];
+
+    decode_gray_frames(
+        input_filepath,
+        sample_rate,
+        HASH_SIZE,
+        HASH_SIZE,
+        None,
+        |time, data| {
+            let hash = FrameHash::new(data);
+            for (index, reference_hash) in reference_hashes.iter().enumerate() {
+                scores[index].push((time, reference_hash.similarity(&hash)));
+            }
+        },
+    );
+
+    scores
+}
+
+/// Lowest score of a matching frame for the reference.
+pub fn min_score(metric: MatchMetric, reference: &FrameReference) -> f32 {
+    let threshold: f32 = match reference.match_threshold.is_empty() {
+        true => metric.default_threshold(),
+        false => reference
+            .match_threshold
+            .parse()
+            .expect("Unable to parse frame match threshold"),
+    };
+    match metric {
+        MatchMetric::Hash => 100.0 * (1.0 - threshold / HASH_BITS as f32),
+        MatchMetric::Ssim | MatchMetric::Psnr => threshold,
+        MatchMetric::Blackframe => reference
+            .blackframe_amount
+            .parse()
+            .expect("Unable to parse frame blackframe amount"),
+    }
+}
+
+/// Frame scores inside `(greater_than, less_than)` window (-1 means no limit) reaching `min_score`.
+pub fn score_hits(
+    scores: &[(f32, f32)],
+    greater_than: f32,
+    less_than: f32,
+    min_score: f32,
+) -> Vec<(f32, f32)> {
+    scores
+        .iter()
+        .filter(|&&(time, score)| {
+            (greater_than == -1.0 || time > greater_than)
+                && (less_than == -1.0 || less_than > time)
+                && score >= min_score
+        })
+        .copied()
+        .collect()
+}
+
+/// Decodes the input once and returns every frame score for each reference.
+pub fn frame_scores(
+    input_filepath: &str,
+    references: &[FrameReference],
+    metric: MatchMetric,
+    sample_rate: f32,
+) -> Vec<Vec<(f32, f32)>> {
+    println!("Frame analyse started... metric {:?} =>", metric);
+
+    match metric {
+        MatchMetric::Hash => hash_scores(input_filepath, references, sample_rate),
+        MatchMetric::Ssim | MatchMetric::Psnr => {
+            metric_scores(input_filepath, references, metric, sample_rate)
+        }
+        MatchMetric::Blackframe => blackframe_scores(input_filepath, references),
+    }
+}
+
+/// Match intervals of one reference from its frame scores.
+pub fn score_intervals(
+    scores: &[(f32, f32)],
+    reference: &FrameReference,
+    metric: MatchMetric,
+    sample_rate: f32,
+) -> Vec<MatchInterval> {
+    let hits = score_hits(
+        scores,
+        reference.greater_than_duration.parse().unwrap_or(-1.0),
+        reference.less_than_duration.parse().unwrap_or(-1.0),
+        min_score(metric, reference),
+    );
+    // sparse samples must still chain into one interval
+    let max_gap = MAX_FRAME_GAP.max(1.5 / sample_rate);
+
+    collect_intervals(&hits, max_gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        blackframe_filter_graph, metric_filter_graph, min_score, score_hits, FrameReference,
+        MatchMetric,
+    };
+
+    fn reference(frame_filepath: &str) -> FrameReference<'_> {
+        FrameReference {
+            frame_filepath,
+            match_threshold: "16",
+            blackframe_amount: "95",
+            blackframe_threshold: "15",
+            greater_than_duration: "",
+            less_than_duration: "",
+        }
+    }
+
+    #[test]
+    fn filter_graph_single_reference() {
+        let filter_graph = blackframe_filter_graph(&[reference("i.jpg")]);
+
+        assert_eq!(
+            filter_graph.graph,
+            "blend=difference:shortest=1,blackframe=95:15"
+        );
+        assert_eq!(filter_graph.filter_names, vec!["Parsed_blackframe_1"]);
+        assert!(filter_graph.outputs.is_empty());
+    }
+
+    #[test]
+    fn filter_graph_intro_and_outro() {
+        let filter_graph = blackframe_filter_graph(&[reference("i.jpg"), reference("o.jpg")]);
+
+        assert_eq!(
+            filter_graph.graph,
+            "[0:v]split=2[v0][v1];[v0][1:v]blend=difference:shortest=1,blackframe=95:15[o0];[v1][2:v]blend=difference:shortest=1,blackframe=95:15[o1]"
+        );
+        assert_eq!(
+            filter_graph.filter_names,
+            vec!["Parsed_blackframe_2", "Parsed_blackframe_4"]
+        );
+        assert_eq!(filter_graph.outputs, vec!["[o0]", "[o1]"]);
+    }
+
+    #[test]
+    fn score_hits_in_window() {
+        let scores = [(1.0, 90.0), (2.0, 70.0), (3.0, 95.0), (4.0, 99.0)];
+        let min = min_score(MatchMetric::Hash, &reference("i.jpg"));
+
+        assert_eq!(min, 75.0);
+        assert_eq!(
+            score_hits(&scores, -1.0, -1.0, min),
+            vec![(1.0, 90.0), (3.0, 95.0), (4.0, 99.0)]
+        );
+        assert_eq!(score_hits(&scores, 1.0, 4.0, min), vec![(3.0, 95.0)]);
+        assert_eq!(
+            min_score(MatchMetric::Blackframe, &reference("i.jpg")),
+            95.0
+        );
+    }
+
+    #[test]
+    fn metric_filter_graph_intro_and_outro() {
+        let filter_graph = metric_filter_graph(
+            &[reference("i.jpg"), reference("o.jpg")],
+            MatchMetric::Ssim,
+            2.0,
+        );
+
+        assert_eq!(
+            filter_graph.graph,
+            "[0:v]fps=2,split=2[v0][v1];[1:v][v0]scale2ref[r0][m0];[m0][r0]ssim=shortest=1,metadata=mode=print[o0];[2:v][v1]scale2ref[r1][m1];[m1][r1]ssim=shortest=1,metadata=mode=print[o1]"
+        );
+        assert_eq!(
+            filter_graph.filter_names,
+            vec!["Parsed_metadata_4", "Parsed_metadata_7"]
+        );
+        assert_eq!(filter_graph.outputs, vec!["[o0]", "[o1]"]);
+    }
+
+    #[test]
+    fn min_score_in_natural_units() {
+        let mut ssim = reference("i.jpg");
+        ssim.match_threshold = "0.95";
+        let mut psnr = reference("i.jpg");
+        psnr.match_threshold = "";
+
+        assert_eq!(min_score(MatchMetric::Ssim, &ssim), 0.95);
+        assert_eq!(min_score(MatchMetric::Psnr, &psnr), 30.0);
     }
 }
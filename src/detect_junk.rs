@@ -0,0 +1,249 @@
+use ffmpeg_sidecar::{
+    command::FfmpegCommand,
+    event::{FfmpegEvent, LogLevel},
+};
+
+use crate::{helpers::parse_log_value, match_interval::MatchInterval};
+
+/// Frames are sampled at this rate (Hz)
+const SAMPLE_RATE: f32 = 1.0;
+/// Average luma change between samples below this counts as static picture
+const MAX_STATIC_YDIF: f32 = 1.0;
+/// Average saturation above this on a static picture looks like color bars
+const MIN_BARS_SATAVG: f32 = 40.0;
+/// Luma spread below this is a solid color picture
+const MAX_SOLID_YRANGE: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub time: f32,
+    pub ydif: f32,
+    pub satavg: f32,
+    pub ylow: f32,
+    pub yhigh: f32,
+}
+
+impl FrameStats {
+    /// Static bars or solid color picture
+    pub fn is_junk(&self) -> bool {
+        self.ydif < MAX_STATIC_YDIF
+            && (self.satavg > MIN_BARS_SATAVG || self.yhigh - self.ylow < MAX_SOLID_YRANGE)
+    }
+}
+
+/// Runs of junk frames, each sample covers one sampling period.
+pub fn junk_intervals(stats: &[FrameStats]) -> Vec<MatchInterval> {
+    let period = 1.0 / SAMPLE_RATE;
+    let mut intervals: Vec<MatchInterval> = Vec::new();
+    let mut previous_junk = false;
+
+    for frame in stats {
+        let junk = frame.is_junk();
+        if junk {
+            match intervals.last_mut() {
+                Some(interval) if previous_junk => {
+                    interval.end = frame.time + period;
+                    interval.frames += 1;
+                }
+                _ => intervals.push(MatchInterval {
+                    start: frame.time,
+                    end: frame.time + period,
+                    peak: frame.satavg,
+                    frames: 1,
+                }),
+            }
+        }
+        previous_junk = junk;
+    }
+
+    intervals
+}
+
+/// End of junk chained from the start of file, intervals closer than `gap` are chained.
+pub fn lead_in_end(intervals: &[MatchInterval], gap: f32) -> Option<f32> {
+    let mut end: Option<f32> = None;
+    let mut sorted = intervals.to_vec();
+    sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    for interval in &sorted {
+        if interval.start <= end.unwrap_or(0.0) + gap {
+            end = Some(end.unwrap_or(0.0).max(interval.end));
+        }
+    }
+
+    end
+}
+
+/// Start of junk chained to the end of file, intervals closer than `gap` are chained.
+pub fn trailing_start(intervals: &[MatchInterval], duration: f32, gap: f32) -> Option<f32> {
+    let mut start: Option<f32> = None;
+    let mut sorted = intervals.to_vec();
+    sorted.sort_by(|a, b| b.end.total_cmp(&a.end));
+
+    for interval in &sorted {
+        if interval.end >= start.unwrap_or(duration) - gap {
+            start = Some(start.unwrap_or(duration).min(interval.start));
+        }
+    }
+
+    start
+}
+
+/// Finds freeze-frame segments and static bars/solid color segments in one pass.
+pub fn detect_junk(input_filepath: &str, duration: f32) -> Vec<MatchInterval> {
+    println!("Junk analyse started... (Duration {}) =>", duration);
+
+    let mut freezes: Vec<MatchInterval> = Vec::new();
+    let mut freeze_start: Option<f32> = None;
+    let mut stats: Vec<FrameStats> = Vec::new();
+
+    FfmpegCommand::new()
+        .args([
+            "-i",
+            input_filepath,
+            "-an", // no audio
+            "-vf",
+            format!(
+                "fps={},freezedetect=n=-60dB:d=2,signalstats,metadata=mode=print",
+                SAMPLE_RATE
+            )
+            .as_str(),
+            "-f",
+            "null",
+            "-",
+        ])
+        .print_command()
+        .spawn()
+        .expect("Unable to spawn child process")
+        .iter()
+        .expect("Unable to obtain child process iterator")
+        .for_each(|e| match e {
+            FfmpegEvent::Log(LogLevel::Error, e) => println!("Error: {}", e),
+            FfmpegEvent::Log(LogLevel::Info, msg) => {
+                if msg.contains("[freezedetect @") {
+                    // e.g. [freezedetect @ 0x55d0c4b1e2c0] [info] lavfi.freezedetect.freeze_start: 0
+                    if let Some(start) = parse_log_value(&msg, "freeze_start:") {
+                        freeze_start = Some(start);
+                    } else if let Some(end) = parse_log_value(&msg, "freeze_end:") {
+                        if let Some(start) = freeze_start.take() {
+                            freezes.push(MatchInterval {
+                                start,
+                                end,
+                                peak: end - start,
+                                frames: 0,
+                            });
+                        }
+                    }
+                } else if let Some(time) = parse_log_value(&msg, "pts_time:") {
+                    // e.g. [Parsed_metadata_3 @ 0x55d0c4b1f400] [info] frame:12   pts:12   pts_time:12
+                    stats.push(FrameStats {
+                        time,
+                        ..Default::default()
+                    });
+                } else if let Some(frame) = stats.last_mut() {
+                    // e.g. [Parsed_metadata_3 @ 0x55d0c4b1f400] [info] lavfi.signalstats.YDIF=0.000000
+                    if let Some(value) = parse_log_value(&msg, "lavfi.signalstats.YDIF=") {
+                        frame.ydif = value;
+                    } else if let Some(value) = parse_log_value(&msg, "lavfi.signalstats.SATAVG=") {
+                        frame.satavg = value;
+                    } else if let Some(value) = parse_log_value(&msg, "lavfi.signalstats.YLOW=") {
+                        frame.ylow = value;
+                    } else if let Some(value) = parse_log_value(&msg, "lavfi.signalstats.YHIGH=") {
+                        frame.yhigh = value;
+                    }
+                }
+            }
+            _ => {}
+        });
+
+    // freeze lasting till the end of file is never closed
+    if let Some(start) = freeze_start {
+        freezes.push(MatchInterval {
+            start,
+            end: duration,
+            peak: duration - start,
+            frames: 0,
+        });
+    }
+
+    let mut intervals = junk_intervals(&stats);
+    intervals.extend(freezes);
+    intervals.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    intervals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{junk_intervals, lead_in_end, trailing_start, FrameStats};
+    use crate::match_interval::MatchInterval;
+
+    fn frame(time: f32, ydif: f32, satavg: f32) -> FrameStats {
+        FrameStats {
+            time,
+            ydif,
+            satavg,
+            ylow: 16.0,
+            yhigh: 235.0,
+        }
+    }
+
+    #[test]
+    fn junk_intervals_of_bars_and_solid() {
+        let mut solid = frame(3.0, 0.0, 0.0);
+        solid.ylow = 16.0;
+        solid.yhigh = 17.0;
+        let stats = [
+            frame(0.0, 0.0, 80.0),
+            frame(1.0, 0.2, 80.0),
+            frame(2.0, 0.1, 80.0),
+            solid,
+            frame(4.0, 12.0, 20.0),
+            frame(5.0, 0.0, 20.0),
+        ];
+
+        let intervals = junk_intervals(&stats);
+
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].start, 0.0);
+        assert_eq!(intervals[0].end, 4.0);
+        assert_eq!(intervals[0].frames, 4);
+    }
+
+    #[test]
+    fn lead_in_end_chains_from_start() {
+        assert_eq!(lead_in_end(&[], 1.0), None);
+        assert_eq!(lead_in_end(&[MatchInterval::new(30.0, 40.0)], 1.0), None);
+        assert_eq!(
+            lead_in_end(
+                &[
+                    MatchInterval::new(6.5, 10.0),
+                    MatchInterval::new(0.0, 6.0),
+                    MatchInterval::new(30.0, 40.0)
+                ],
+                1.0
+            ),
+            Some(10.0)
+        );
+    }
+
+    #[test]
+    fn trailing_start_chains_to_end() {
+        assert_eq!(trailing_start(&[], 100.0, 1.0), None);
+        assert_eq!(
+            trailing_start(&[MatchInterval::new(30.0, 40.0)], 100.0, 1.0),
+            None
+        );
+        assert_eq!(
+            trailing_start(
+                &[
+                    MatchInterval::new(90.0, 95.5),
+                    MatchInterval::new(96.0, 100.0)
+                ],
+                100.0,
+                1.0
+            ),
+            Some(90.0)
+        );
+    }
+}
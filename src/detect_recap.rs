@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    decode_frames::decode_gray_frames,
+    match_interval::{collect_intervals, MAX_FRAME_GAP},
+    perceptual_hash::{FrameHash, HASH_SIZE},
+};
+
+/// Frames closer than this hash distance (0..64) show the same picture
+const MAX_RECAP_DISTANCE: f32 = 10.0;
+/// Luma standard deviation below this is a flat picture (black, fade) that matches anything
+const MIN_CONTENT_STDDEV: f32 = 8.0;
+/// Unique content shorter than this (in seconds) is not a cold open
+const MIN_COLD_OPEN_SECONDS: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HashedFrame {
+    pub time: f32,
+    pub hash: FrameHash,
+    pub flat: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameKind {
+    /// also present in the previous episode
+    Shared,
+    Unique,
+    Flat,
+}
+
+fn stddev(gray: &[u8]) -> f32 {
+    let mean = gray.iter().map(|&v| v as f32).sum::<f32>() / gray.len() as f32;
+    let variance = gray
+        .iter()
+        .map(|&v| (v as f32 - mean) * (v as f32 - mean))
+        .sum::<f32>()
+        / gray.len() as f32;
+
+    variance.sqrt()
+}
+
+/// Perceptual hashes of the whole input sampled at `sample_rate` Hz.
+pub fn hash_frames(input_filepath: &str, sample_rate: f32) -> Vec<HashedFrame> {
+    println!("Hash frames of {} =>", input_filepath);
+    let mut frames: Vec<HashedFrame> = Vec::new();
+    decode_gray_frames(
+        input_filepath,
+        sample_rate,
+        HASH_SIZE,
+        HASH_SIZE,
+        None,
+        |time, data| {
+            frames.push(HashedFrame {
+                time,
+                hash: FrameHash::new(data),
+                flat: stddev(data) < MIN_CONTENT_STDDEV,
+            })
+        },
+    );
+
+    frames
+}
+
+/// Kind of every `current` frame before `until` compared with all `previous` frames.
+pub fn classify_frames(
+    current: &[HashedFrame],
+    previous: &[HashedFrame],
+    until: f32,
+) -> Vec<(f32, FrameKind)> {
+    current
+        .iter()
+        .take_while(|frame| frame.time < until)
+        .map(|frame| {
+            let kind = match frame.flat {
+                true => FrameKind::Flat,
+                false => match previous
+                    .iter()
+                    .filter(|other| !other.flat)
+                    .any(|other| frame.hash.distance(&other.hash) <= MAX_RECAP_DISTANCE)
+                {
+                    true => FrameKind::Shared,
+                    false => FrameKind::Unique,
+                },
+            };
+            (frame.time, kind)
+        })
+        .collect()
+}
+
+/// Cuts keeping only cold open content before `intro_time`: new trim start and segments to remove.
+/// `None` when there is no unique content long enough, then the intro cut is used as is.
+pub fn recap_cuts(
+    frames: &[(f32, FrameKind)],
+    intro_time: f32,
+    sample_rate: f32,
+) -> Option<(f32, Vec<(f32, f32)>)> {
+    let period = 1.0 / sample_rate;
+    let unique_hits: Vec<(f32, f32)> = frames
+        .iter()
+        .filter(|(_, kind)| *kind == FrameKind::Unique)
+        .map(|&(time, _)| (time, 0.0))
+        .collect();
+    let cold_opens: Vec<(f32, f32)> =
+        collect_intervals(&unique_hits, MAX_FRAME_GAP.max(1.5 * period))
+            .iter()
+            // a sample stands for the whole period
+            .map(|interval| (interval.start, (interval.end + period).min(intro_time)))
+            .filter(|(start, end)| end - start >= MIN_COLD_OPEN_SECONDS)
+            .collect();
+    let first = cold_opens.first()?;
+
+    let mut removed: Vec<(f32, f32)> = cold_opens
+        .windows(2)
+        .map(|pair| (pair[0].1, pair[1].0))
+        .collect();
+    let last_end = cold_opens[cold_opens.len() - 1].1;
+    if last_end < intro_time {
+        removed.push((last_end, intro_time));
+    }
+
+    Some((first.0, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify_frames, recap_cuts, FrameKind, HashedFrame};
+    use crate::perceptual_hash::FrameHash;
+
+    fn frame(time: f32, dhash: u64, flat: bool) -> HashedFrame {
+        HashedFrame {
+            time,
+            hash: FrameHash {
+                dhash,
+                phash: dhash,
+            },
+            flat,
+        }
+    }
+
+    #[test]
+    fn classify_frames_against_previous() {
+        let previous = [frame(0.0, 0, true), frame(1.0, 0xff, false)];
+        let current = [
+            frame(0.0, 0xff, false),
+            frame(1.0, u64::MAX, false),
+            frame(2.0, 0, true),
+            frame(3.0, 0xff, false),
+        ];
+
+        assert_eq!(
+            classify_frames(&current, &previous, 3.0),
+            vec![
+                (0.0, FrameKind::Shared),
+                (1.0, FrameKind::Unique),
+                (2.0, FrameKind::Flat)
+            ]
+        );
+    }
+
+    #[test]
+    fn recap_cuts_keep_cold_open() {
+        // recap 0..20, black 20..22, cold open 22..52, intro 52..60
+        let frames: Vec<(f32, FrameKind)> = (0..60)
+            .map(|t| {
+                let kind = match t {
+                    0..=19 => FrameKind::Shared,
+                    20..=21 => FrameKind::Flat,
+                    22..=51 => FrameKind::Unique,
+                    _ => FrameKind::Shared,
+                };
+                (t as f32, kind)
+            })
+            .collect();
+
+        assert_eq!(
+            recap_cuts(&frames, 60.0, 1.0),
+            Some((22.0, vec![(52.0, 60.0)]))
+        );
+    }
+
+    #[test]
+    fn recap_cuts_without_cold_open() {
+        let frames: Vec<(f32, FrameKind)> = (0..60)
+            .map(|t| match t {
+                // too short for a cold open
+                30..=34 => (t as f32, FrameKind::Unique),
+                _ => (t as f32, FrameKind::Shared),
+            })
+            .collect();
+
+        assert_eq!(recap_cuts(&frames, 60.0, 1.0), None);
+    }
+}
@@ -0,0 +1,71 @@
+use ffmpeg_sidecar::{
+    command::FfmpegCommand,
+    event::{FfmpegEvent, LogLevel},
+};
+
+use crate::{helpers::parse_log_value, match_interval::MatchInterval};
+
+/// Runs silencedetect over the input, the interval peak is the silence duration.
+pub fn detect_silence(
+    input_filepath: &str,
+    duration: f32,
+    noise: &str,
+    silence_duration: &str,
+) -> Vec<MatchInterval> {
+    println!("Silence analyse started... (noise {noise}, duration {silence_duration}) =>");
+
+    let mut intervals: Vec<MatchInterval> = Vec::new();
+    let mut silence_start: Option<f32> = None;
+
+    FfmpegCommand::new()
+        .args([
+            "-i",
+            input_filepath,
+            "-vn", // no video
+            "-af",
+            format!("silencedetect=noise={}:d={}", noise, silence_duration).as_str(),
+            "-f",
+            "null",
+            "-",
+        ])
+        .print_command()
+        .spawn()
+        .expect("Unable to spawn child process")
+        .iter()
+        .expect("Unable to obtain child process iterator")
+        .for_each(|e| match e {
+            FfmpegEvent::Log(LogLevel::Error, e) => println!("Error: {}", e),
+            FfmpegEvent::Log(LogLevel::Info, msg) => {
+                // e.g. [silencedetect @ 0x5581c1f3a100] [info] silence_start: 84.2
+                // [silencedetect @ 0x5581c1f3a100] [info] silence_end: 86.1 | silence_duration: 1.9
+                if !msg.contains("[silencedetect @") {
+                    return;
+                }
+                if let Some(start) = parse_log_value(&msg, "silence_start:") {
+                    silence_start = Some(start.max(0.0));
+                } else if let Some(end) = parse_log_value(&msg, "silence_end:") {
+                    if let Some(start) = silence_start.take() {
+                        intervals.push(MatchInterval {
+                            start,
+                            end,
+                            peak: end - start,
+                            frames: 0,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        });
+
+    // silence lasting till the end of file is never closed
+    if let Some(start) = silence_start {
+        intervals.push(MatchInterval {
+            start,
+            end: duration,
+            peak: duration - start,
+            frames: 0,
+        });
+    }
+
+    intervals
+}
@@ -0,0 +1,152 @@
+use std::{
+    io::{BufReader, Read},
+    process::{Command, Stdio},
+};
+
+use crate::{detector::Side, match_interval::MatchInterval};
+
+/// Parses `ffprobe -select_streams s:0 -show_entries packet=pts_time,duration_time -of csv=p=0` output
+/// into sorted (start, end) subtitle events, unknown duration makes an instant event.
+pub fn parse_subtitle_events(output: &str) -> Vec<(f32, f32)> {
+    let mut events: Vec<(f32, f32)> = output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().split(',');
+            let start: f32 = parts.next()?.parse().ok()?;
+            let duration: f32 = parts.next().and_then(|d| d.parse().ok()).unwrap_or(0.0);
+            Some((start, start + duration))
+        })
+        .collect();
+    events.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    events
+}
+
+/// Periods without subtitles longer than `min_gap`, including before the first and after the last event.
+/// Interval end is the first line after the gap, start is where the last line before it ends.
+pub fn subtitle_gaps(events: &[(f32, f32)], duration: f32, min_gap: f32) -> Vec<MatchInterval> {
+    let mut gaps: Vec<MatchInterval> = Vec::new();
+    let mut previous_end: f32 = 0.0;
+
+    for &(start, end) in events.iter().chain([(duration, duration)].iter()) {
+        if start - previous_end > min_gap {
+            gaps.push(MatchInterval {
+                start: previous_end,
+                end: start,
+                peak: start - previous_end,
+                frames: 0,
+            });
+        }
+        previous_end = previous_end.max(end);
+    }
+
+    gaps
+}
+
+/// Gap ends (first line after the gap) for intro, gap starts for outro,
+/// inside `(greater_than, less_than)` window (-1 means no limit) as instant intervals to snap cuts to.
+pub fn gap_boundaries(
+    gaps: &[MatchInterval],
+    side: Side,
+    greater_than: f32,
+    less_than: f32,
+) -> Vec<MatchInterval> {
+    gaps.iter()
+        .map(|gap| side.boundary(gap))
+        .filter(|&time| {
+            (greater_than == -1.0 || time > greater_than) && (less_than == -1.0 || less_than > time)
+        })
+        .map(|time| MatchInterval {
+            start: time,
+            end: time,
+            peak: 0.0,
+            frames: 0,
+        })
+        .collect()
+}
+
+/// Finds long gaps between events of the first subtitle stream.
+pub fn detect_subtitle_gaps(
+    input_filepath: &str,
+    duration: f32,
+    min_gap: f32,
+) -> Vec<MatchInterval> {
+    println!("Subtitles analyse started... (Duration {}) =>", duration);
+
+    let mut child = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "s:0",
+            "-show_entries",
+            "packet=pts_time,duration_time",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(input_filepath)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stdout = child.stdout.take().expect("No child stdout");
+
+    let mut reader = BufReader::new(stdout);
+
+    let mut result = String::new();
+
+    reader.read_to_string(&mut result).unwrap();
+
+    let status = child.wait().unwrap();
+
+    if !status.success() {
+        panic!("Unable to read {input_filepath} subtitles! Result is {result}");
+    }
+
+    subtitle_gaps(&parse_subtitle_events(&result), duration, min_gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gap_boundaries, parse_subtitle_events, subtitle_gaps};
+    use crate::detector::Side;
+
+    #[test]
+    fn parse_subtitle_events_with_durations() {
+        let output = "95.200000,2.500000\n3.000000,1.000000\n120.000000,N/A\n\n";
+
+        assert_eq!(
+            parse_subtitle_events(output),
+            vec![(3.0, 4.0), (95.2, 97.7), (120.0, 120.0)]
+        );
+    }
+
+    #[test]
+    fn subtitle_gaps_longer_than_min() {
+        // cold open dialogue, intro song without lines, dialogue again
+        let events = [(3.0, 4.0), (5.0, 8.0), (95.2, 97.7), (99.0, 101.0)];
+        let gaps = subtitle_gaps(&events, 130.0, 10.0);
+
+        assert_eq!(gaps.len(), 2);
+        assert_eq!((gaps[0].start, gaps[0].end), (8.0, 95.2));
+        assert_eq!((gaps[1].start, gaps[1].end), (101.0, 130.0));
+        assert_eq!(subtitle_gaps(&[], 130.0, 10.0)[0].end, 130.0);
+
+        let intro_boundaries = gap_boundaries(&gaps, Side::Intro, -1.0, -1.0);
+        assert_eq!(intro_boundaries.len(), 2);
+        assert_eq!(
+            (intro_boundaries[0].start, intro_boundaries[0].end),
+            (95.2, 95.2)
+        );
+        let outro_boundaries = gap_boundaries(&gaps, Side::Outro, -1.0, -1.0);
+        assert_eq!(
+            (outro_boundaries[1].start, outro_boundaries[1].end),
+            (101.0, 101.0)
+        );
+        // cold open gap end is outside intro window
+        let windowed = gap_boundaries(&gaps, Side::Intro, 30.0, 120.0);
+        assert_eq!(windowed.len(), 1);
+        assert_eq!(windowed[0].start, 95.2);
+        assert!(gap_boundaries(&gaps, Side::Outro, 110.0, -1.0).is_empty());
+    }
+}
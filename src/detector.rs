@@ -0,0 +1,605 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    cache::{file_hash, FileCache},
+    detect_audio::{detect_audio, AudioReference},
+    detect_black::{detect_black_scenes, BlackScenes},
+    detect_chapters::detect_chapters,
+    detect_credits::detect_credits,
+    detect_frame::{frame_scores, score_intervals, FrameReference, MatchMetric},
+    detect_junk::detect_junk,
+    detect_silence::detect_silence,
+    detect_subtitles::detect_subtitle_gaps,
+    match_interval::MatchInterval,
+    probe_media::MediaInfo,
+};
+
+/// Finds scored intervals in the input, e.g. where a reference frame matches.
+/// Results are kept in the input's cache.
+pub trait Detector {
+    fn name(&self) -> &'static str;
+    fn detect(
+        &self,
+        input_filepath: &str,
+        media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval>;
+}
+
+/// Cached result of every reference, the ones missing from the cache are detected together.
+fn cached_per_reference<R: Copy, T: Serialize + DeserializeOwned>(
+    cache: &mut FileCache,
+    kind: &str,
+    references: &[R],
+    parameters: impl Fn(&R) -> Vec<String>,
+    detect: impl FnOnce(&[R]) -> Vec<T>,
+) -> Vec<T> {
+    let parameters: Vec<Vec<String>> = references.iter().map(parameters).collect();
+    let parameters: Vec<Vec<&str>> = parameters
+        .iter()
+        .map(|reference_parameters| reference_parameters.iter().map(|p| p.as_str()).collect())
+        .collect();
+    let mut results: Vec<Option<T>> = parameters
+        .iter()
+        .map(|reference_parameters| cache.cached(kind, reference_parameters))
+        .collect();
+    let missing: Vec<usize> = (0..references.len())
+        .filter(|&index| results[index].is_none())
+        .collect();
+    if !missing.is_empty() {
+        let missing_references: Vec<R> = missing.iter().map(|&index| references[index]).collect();
+        for (&index, result) in missing.iter().zip(detect(&missing_references)) {
+            cache.store(kind, &parameters[index], &result);
+            results[index] = Some(result);
+        }
+    }
+
+    results.into_iter().flatten().collect()
+}
+
+/// Frame scores of every reference, cached by metric, reference image and the parameters the metric uses:
+/// sample rate for hash, ssim and psnr, blackframe amount and threshold for blackframe which reports hits only.
+/// Windows and match thresholds apply to the scores afterwards.
+pub fn cached_frame_scores(
+    cache: &mut FileCache,
+    input_filepath: &str,
+    references: &[FrameReference],
+    metric: MatchMetric,
+    sample_rate: f32,
+) -> Vec<Vec<(f32, f32)>> {
+    // json has no infinity, e.g. psnr of identical frames
+    let scores: Vec<Vec<(f32, Option<f32>)>> = cached_per_reference(
+        cache,
+        "frame",
+        references,
+        |reference| {
+            let mut key = vec![format!("{:?}", metric), file_hash(reference.frame_filepath)];
+            match metric {
+                MatchMetric::Blackframe => {
+                    key.push(reference.blackframe_amount.to_owned());
+                    key.push(reference.blackframe_threshold.to_owned());
+                }
+                MatchMetric::Hash | MatchMetric::Ssim | MatchMetric::Psnr => {
+                    key.push(sample_rate.to_string())
+                }
+            }
+            key
+        },
+        |references| {
+            frame_scores(input_filepath, references, metric, sample_rate)
+                .into_iter()
+                .map(|reference_scores| {
+                    reference_scores
+                        .into_iter()
+                        .map(|(time, score)| (time, Some(score).filter(|s| s.is_finite())))
+                        .collect()
+                })
+                .collect()
+        },
+    );
+
+    scores
+        .into_iter()
+        .map(|reference_scores| {
+            reference_scores
+                .into_iter()
+                .map(|(time, score)| (time, score.unwrap_or(f32::INFINITY)))
+                .collect()
+        })
+        .collect()
+}
+
+/// Audio matches of every reference, cached by clip, window and bit error rate.
+pub fn cached_audio_matches(
+    cache: &mut FileCache,
+    input_filepath: &str,
+    references: &[AudioReference],
+    max_bit_error_rate: &str,
+) -> Vec<Vec<MatchInterval>> {
+    cached_per_reference(
+        cache,
+        "audio",
+        references,
+        |reference| {
+            vec![
+                max_bit_error_rate.to_owned(),
+                file_hash(reference.clip_filepath),
+                reference.greater_than_duration.to_owned(),
+                reference.less_than_duration.to_owned(),
+            ]
+        },
+        |references| detect_audio(input_filepath, references, max_bit_error_rate),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    /// boundary is the end of an interval
+    Intro,
+    /// boundary is the start of an interval
+    Outro,
+}
+
+impl Side {
+    pub fn boundary(&self, interval: &MatchInterval) -> f32 {
+        match self {
+            Side::Intro => interval.end,
+            Side::Outro => interval.start,
+        }
+    }
+}
+
+pub struct FrameDetector {
+    pub frame_filepath: String,
+    pub blackframe_amount: String,
+    pub blackframe_threshold: String,
+    pub metric: MatchMetric,
+    pub match_threshold: String,
+    pub sample_rate: String,
+    pub greater_than_duration: String,
+    pub less_than_duration: String,
+}
+
+impl Detector for FrameDetector {
+    fn name(&self) -> &'static str {
+        "frame"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        _media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        let reference = FrameReference {
+            frame_filepath: &self.frame_filepath,
+            blackframe_amount: &self.blackframe_amount,
+            blackframe_threshold: &self.blackframe_threshold,
+            match_threshold: &self.match_threshold,
+            greater_than_duration: &self.greater_than_duration,
+            less_than_duration: &self.less_than_duration,
+        };
+
+        let sample_rate: f32 = self
+            .sample_rate
+            .parse()
+            .expect("Unable to parse frame sample rate");
+        let scores = cached_frame_scores(
+            cache,
+            input_filepath,
+            &[reference],
+            self.metric,
+            sample_rate,
+        );
+
+        score_intervals(&scores[0], &reference, self.metric, sample_rate)
+    }
+}
+
+pub struct AudioDetector {
+    pub clip_filepath: String,
+    pub max_bit_error_rate: String,
+    pub greater_than_duration: String,
+    pub less_than_duration: String,
+}
+
+impl Detector for AudioDetector {
+    fn name(&self) -> &'static str {
+        "audio"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        _media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        let reference = AudioReference {
+            clip_filepath: &self.clip_filepath,
+            greater_than_duration: &self.greater_than_duration,
+            less_than_duration: &self.less_than_duration,
+        };
+
+        cached_audio_matches(
+            cache,
+            input_filepath,
+            &[reference],
+            &self.max_bit_error_rate,
+        )
+        .remove(0)
+    }
+}
+
+pub struct SilenceDetector {
+    pub noise: String,
+    pub silence_duration: String,
+}
+
+impl Detector for SilenceDetector {
+    fn name(&self) -> &'static str {
+        "silence"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        cache.detection("silence", &[&self.noise, &self.silence_duration], || {
+            detect_silence(
+                input_filepath,
+                media_info.duration,
+                &self.noise,
+                &self.silence_duration,
+            )
+        })
+    }
+}
+
+pub struct BlackDetector {
+    pub black_duration: String,
+    pub black_threshold: String,
+}
+
+impl Detector for BlackDetector {
+    fn name(&self) -> &'static str {
+        "black"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        _media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        let black_scenes: BlackScenes = cache.detection(
+            "black",
+            &[&self.black_duration, &self.black_threshold, ""],
+            || {
+                detect_black_scenes(
+                    input_filepath,
+                    &self.black_duration,
+                    &self.black_threshold,
+                    None,
+                )
+            },
+        );
+
+        black_scenes.black
+    }
+}
+
+pub struct ChapterDetector {
+    /// chapter title parts, e.g. `opening`
+    pub titles: Vec<String>,
+}
+
+impl Detector for ChapterDetector {
+    fn name(&self) -> &'static str {
+        "chapter"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        _media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        let titles: Vec<&str> = self.titles.iter().map(|title| title.as_str()).collect();
+
+        cache.detection("chapter", &titles, || {
+            detect_chapters(input_filepath, &titles)
+        })
+    }
+}
+
+/// Long gaps between subtitle lines, e.g. intro song without dialogue
+pub struct SubtitleDetector {
+    /// seconds without subtitles
+    pub min_gap: f32,
+}
+
+impl Detector for SubtitleDetector {
+    fn name(&self) -> &'static str {
+        "subtitles"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        if !media_info.has_subtitles {
+            return Vec::new();
+        }
+
+        cache.detection("subtitles", &[&self.min_gap.to_string()], || {
+            detect_subtitle_gaps(input_filepath, media_info.duration, self.min_gap)
+        })
+    }
+}
+
+/// Scrolling credits near the end of file
+pub struct CreditsDetector;
+
+impl Detector for CreditsDetector {
+    fn name(&self) -> &'static str {
+        "credits"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        cache.detection("credits", &[], || {
+            detect_credits(input_filepath, media_info.duration)
+        })
+    }
+}
+
+/// Color bars, slates and frozen frames at start and end of file
+pub struct JunkDetector;
+
+impl Detector for JunkDetector {
+    fn name(&self) -> &'static str {
+        "junk"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        cache.detection("junk", &[], || {
+            detect_junk(input_filepath, media_info.duration)
+        })
+    }
+}
+
+/// Fixed time given on the command line, seconds from start (negative from end).
+pub struct ManualDetector {
+    pub time: f32,
+}
+
+impl Detector for ManualDetector {
+    fn name(&self) -> &'static str {
+        "manual"
+    }
+
+    fn detect(
+        &self,
+        _input_filepath: &str,
+        media_info: &MediaInfo,
+        _cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        let time = match self.time < 0.0 {
+            true => media_info.duration + self.time,
+            false => self.time,
+        };
+
+        vec![MatchInterval {
+            start: time,
+            end: time,
+            peak: 100.0,
+            frames: 0,
+        }]
+    }
+}
+
+/// Boundary candidate: detector index, time and weight
+pub type Candidate = (usize, f32, f32);
+
+/// Picks the time most detectors agree on. Candidates closer than `tolerance` form a cluster,
+/// every detector adds its weight once per cluster.
+/// Returns time (weighted mean), score (sum of weights) and number of agreeing detectors.
+pub fn vote(candidates: &[Candidate], tolerance: f32) -> Option<(f32, f32, u32)> {
+    let mut best: Option<(f32, f32, u32, f32)> = None;
+
+    for &(_, seed, _) in candidates {
+        // nearest candidate of every detector around the seed
+        let mut chosen: Vec<Candidate> = Vec::new();
+        for &candidate in candidates {
+            let (detector, time, _) = candidate;
+            if (time - seed).abs() > tolerance {
+                continue;
+            }
+            match chosen.iter_mut().find(|c| c.0 == detector) {
+                Some(c) if (time - seed).abs() < (c.1 - seed).abs() => *c = candidate,
+                Some(_) => {}
+                None => chosen.push(candidate),
+            }
+        }
+        let score: f32 = chosen.iter().map(|c| c.2).sum();
+        if score <= 0.0 {
+            continue;
+        }
+        let time = chosen.iter().map(|c| c.1 * c.2).sum::<f32>() / score;
+        let spread: f32 = chosen.iter().map(|c| (c.1 - time).abs()).sum();
+        let better = match best {
+            None => true,
+            Some((_, best_score, _, best_spread)) => {
+                score > best_score || (score == best_score && spread < best_spread)
+            }
+        };
+        if better {
+            best = Some((time, score, chosen.len() as u32, spread));
+        }
+    }
+
+    best.map(|(time, score, count, _)| (time, score, count))
+}
+
+/// Weighted voting over several detectors for one side of the file.
+pub struct VotingDetector {
+    pub detectors: Vec<(Box<dyn Detector>, f32)>,
+    pub side: Side,
+    /// seconds between candidates that still agree
+    pub tolerance: f32,
+    /// candidates outside `(greater_than, less_than)` are ignored, -1 means no limit
+    pub greater_than: f32,
+    pub less_than: f32,
+}
+
+impl Detector for VotingDetector {
+    fn name(&self) -> &'static str {
+        "vote"
+    }
+
+    fn detect(
+        &self,
+        input_filepath: &str,
+        media_info: &MediaInfo,
+        cache: &mut FileCache,
+    ) -> Vec<MatchInterval> {
+        let mut candidates: Vec<Candidate> = Vec::new();
+        for (index, (detector, weight)) in self.detectors.iter().enumerate() {
+            let intervals = detector.detect(input_filepath, media_info, cache);
+            println!("\n{} {:?}", detector.name(), intervals);
+            candidates.extend(
+                intervals
+                    .iter()
+                    .map(|interval| self.side.boundary(interval))
+                    .filter(|&time| {
+                        (self.greater_than == -1.0 || time > self.greater_than)
+                            && (self.less_than == -1.0 || self.less_than > time)
+                    })
+                    .map(|time| (index, time, *weight)),
+            );
+        }
+
+        match vote(&candidates, self.tolerance) {
+            Some((time, score, count)) => vec![MatchInterval {
+                start: time,
+                end: time,
+                peak: score,
+                frames: count,
+            }],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Parses `frame:2,audio,silence:0.5` into detector names with weights (1 by default).
+pub fn parse_detectors_spec(spec: &str) -> Vec<(String, f32)> {
+    spec.split(',')
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .map(|part| match part.split_once(':') {
+            Some((name, weight)) => (
+                name.trim().to_owned(),
+                weight
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Unable to parse detector weight {}", part)),
+            ),
+            None => (part.to_owned(), 1.0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cached_per_reference, parse_detectors_spec, vote, Detector, ManualDetector};
+    use crate::{
+        cache::{CacheMode, FileCache},
+        probe_media::MediaInfo,
+    };
+
+    #[test]
+    fn parse_detectors_spec_with_weights() {
+        assert!(parse_detectors_spec("").is_empty());
+        assert_eq!(
+            parse_detectors_spec("frame:2, audio,silence:0.5"),
+            vec![
+                ("frame".to_owned(), 2.0),
+                ("audio".to_owned(), 1.0),
+                ("silence".to_owned(), 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unable to parse detector weight frame:x")]
+    fn parse_detectors_spec_panic_weight() {
+        parse_detectors_spec("frame:x");
+    }
+
+    #[test]
+    fn vote_for_agreement() {
+        assert_eq!(vote(&[], 2.0), None);
+
+        // frame and audio agree near 60, silence has many candidates
+        let candidates = [
+            (0, 60.0, 2.0),
+            (1, 61.0, 1.0),
+            (2, 20.0, 0.5),
+            (2, 60.5, 0.5),
+            (2, 100.0, 0.5),
+        ];
+        let (time, score, count) = vote(&candidates, 2.0).unwrap();
+        assert_eq!(score, 3.5);
+        assert_eq!(count, 3);
+        assert!((time - 60.357).abs() < 0.01);
+    }
+
+    #[test]
+    fn manual_detector_from_end() {
+        let media_info = MediaInfo {
+            duration: 100.0,
+            has_video: true,
+            has_audio: true,
+            has_subtitles: false,
+        };
+
+        let mut cache = FileCache::open("Cargo.toml", CacheMode::Off);
+
+        assert_eq!(
+            ManualDetector { time: -20.0 }.detect("", &media_info, &mut cache)[0].start,
+            80.0
+        );
+    }
+
+    #[test]
+    fn cached_per_reference_detects_missing_together() {
+        let mut cache = FileCache::open("Cargo.toml", CacheMode::Off);
+        let parameters = |reference: &u32| vec![reference.to_string()];
+
+        let results = cached_per_reference(&mut cache, "test", &[1, 2], parameters, |references| {
+            assert_eq!(references, [1, 2]);
+            references.iter().map(|r| r * 10).collect()
+        });
+        assert_eq!(results, vec![10, 20]);
+
+        let results =
+            cached_per_reference(&mut cache, "test", &[3, 2, 1], parameters, |references| {
+                assert_eq!(references, [3]);
+                vec![30]
+            });
+        assert_eq!(results, vec![30, 20, 10]);
+    }
+}
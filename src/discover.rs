@@ -0,0 +1,315 @@
+use crate::{
+    detect_audio::decode_audio,
+    fingerprint::{bit_error_rate, fingerprint, frame_duration},
+    match_interval::MatchInterval,
+};
+
+/// Shared segments shorter than this (in seconds) are ignored
+const MIN_SHARED_SECONDS: f32 = 10.0;
+
+/// Fingerprint frames compared as one block, about one second
+fn block_frames() -> usize {
+    (1.0 / frame_duration()).round() as usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SharedSegment {
+    /// first frame of the segment in `a`
+    pub a_start: usize,
+    /// first frame of the segment in `b`
+    pub b_start: usize,
+    /// segment length in frames
+    pub len: usize,
+}
+
+/// Longest segment present in both fingerprints, compared block by block at every alignment.
+/// One mismatching block inside a run is tolerated.
+pub fn longest_shared(
+    a: &[u32],
+    b: &[u32],
+    block: usize,
+    max_bit_error_rate: f32,
+) -> Option<SharedSegment> {
+    if a.len() < block || b.len() < block {
+        return None;
+    }
+    let mut longest: Option<SharedSegment> = None;
+
+    // b index = a index + shift
+    for shift in -(a.len() as isize - block as isize)..=(b.len() as isize - block as isize) {
+        let a_first = (-shift).max(0) as usize;
+        let mut run_start: Option<usize> = None;
+        let mut run_end = 0;
+        let mut misses = 0;
+        let mut a_index = a_first;
+        loop {
+            let b_index = (a_index as isize + shift) as usize;
+            let in_range = a_index + block <= a.len() && b_index + block <= b.len();
+            let matched = in_range
+                && bit_error_rate(
+                    &b[b_index..b_index + block],
+                    &a[a_index..a_index + block],
+                    0,
+                ) < max_bit_error_rate;
+            if matched {
+                run_start.get_or_insert(a_index);
+                run_end = a_index + block;
+                misses = 0;
+            } else if let Some(start) = run_start {
+                misses += 1;
+                if misses > 1 || !in_range {
+                    let len = run_end - start;
+                    if longest.is_none_or(|segment| len > segment.len) {
+                        longest = Some(SharedSegment {
+                            a_start: start,
+                            b_start: (start as isize + shift) as usize,
+                            len,
+                        });
+                    }
+                    run_start = None;
+                    misses = 0;
+                }
+            }
+            if !in_range {
+                break;
+            }
+            a_index += block;
+        }
+    }
+
+    longest
+}
+
+/// Longest `[start, end)` range covered by at least `min_count` of the `ranges`.
+pub fn majority_range(ranges: &[(usize, usize)], min_count: usize) -> Option<(usize, usize)> {
+    let mut points: Vec<usize> = ranges.iter().flat_map(|&(s, e)| [s, e]).collect();
+    points.sort_unstable();
+    points.dedup();
+
+    let mut best: Option<(usize, usize)> = None;
+    let mut current: Option<(usize, usize)> = None;
+    for pair in points.windows(2) {
+        let (s, e) = (pair[0], pair[1]);
+        let count = ranges
+            .iter()
+            .filter(|&&(rs, re)| rs <= s && e <= re)
+            .count();
+        if count >= min_count {
+            current = match current {
+                Some((cs, _)) => Some((cs, e)),
+                None => Some((s, e)),
+            };
+            if let Some((cs, ce)) = current {
+                if best.is_none_or(|(bs, be)| ce - cs > be - bs) {
+                    best = current;
+                }
+            }
+        } else {
+            current = None;
+        }
+    }
+
+    best
+}
+
+/// Per file `[start, end)` frames of the segment shared by most files, with the share (in percent)
+/// of files covering it. `shared[i][j]` is the longest segment of file `i` (a) found in file `j` (b).
+/// Every file is tried as the pivot so that one episode without the intro does not decide for the others.
+pub fn shared_ranges(shared: &[Vec<Option<SharedSegment>>]) -> (Vec<Option<(usize, usize)>>, f32) {
+    let count = shared.len();
+    let min_count = (count - 1).div_ceil(2);
+    // pivot, range and files covering it
+    let mut best: Option<(usize, (usize, usize), Vec<usize>)> = None;
+
+    for (pivot, segments) in shared.iter().enumerate() {
+        let ranges: Vec<(usize, usize)> = segments
+            .iter()
+            .flatten()
+            .map(|segment| (segment.a_start, segment.a_start + segment.len))
+            .collect();
+        let Some((start, end)) = majority_range(&ranges, min_count) else {
+            continue;
+        };
+        let covering: Vec<usize> = (0..count)
+            .filter(|&other| {
+                segments[other].is_some_and(|segment| {
+                    segment.a_start <= start && end <= segment.a_start + segment.len
+                })
+            })
+            .collect();
+        let better = match &best {
+            None => true,
+            Some((_, (best_start, best_end), best_covering)) => {
+                covering.len() > best_covering.len()
+                    || (covering.len() == best_covering.len()
+                        && end - start > best_end - best_start)
+            }
+        };
+        if better {
+            best = Some((pivot, (start, end), covering));
+        }
+    }
+
+    let Some((pivot, (start, end), covering)) = best else {
+        return (vec![None; count], 0.0);
+    };
+    let mut ranges: Vec<Option<(usize, usize)>> = vec![None; count];
+    ranges[pivot] = Some((start, end));
+    for &other in &covering {
+        if let Some(segment) = shared[pivot][other] {
+            let b_start = segment.b_start + (start - segment.a_start);
+            ranges[other] = Some((b_start, b_start + (end - start)));
+        }
+    }
+    let agreement = 100.0 * (covering.len() + 1) as f32 / count as f32;
+
+    (ranges, agreement)
+}
+
+/// Finds the segment shared by most of the files and returns it for every file (in seconds).
+pub fn discover_intros(
+    file_pathes: &[String],
+    minutes: &str,
+    max_bit_error_rate: &str,
+) -> Vec<Option<MatchInterval>> {
+    let minutes: f32 = minutes.parse().expect("Unable to parse discover minutes");
+    let max_bit_error_rate: f32 = max_bit_error_rate
+        .parse()
+        .expect("Unable to parse audio match bit error rate");
+    if file_pathes.len() < 2 {
+        panic!("Discover requires at least two files of the same series");
+    }
+
+    let fingerprints: Vec<Vec<u32>> = file_pathes
+        .iter()
+        .map(|file_path| {
+            println!("Discover fingerprint {} =>", file_path);
+            fingerprint(&decode_audio(file_path, Some(minutes * 60.0)))
+        })
+        .collect();
+
+    let block = block_frames();
+    let frame_duration = frame_duration();
+    let count = fingerprints.len();
+    let mut shared: Vec<Vec<Option<SharedSegment>>> = vec![vec![None; count]; count];
+    for a in 0..count {
+        for b in a + 1..count {
+            let segment = longest_shared(
+                &fingerprints[a],
+                &fingerprints[b],
+                block,
+                max_bit_error_rate,
+            )
+            .filter(|segment| segment.len as f32 * frame_duration >= MIN_SHARED_SECONDS);
+            shared[a][b] = segment;
+            shared[b][a] = segment.map(|segment| SharedSegment {
+                a_start: segment.b_start,
+                b_start: segment.a_start,
+                len: segment.len,
+            });
+        }
+    }
+    println!("Discovered shared segments {:?}", shared);
+
+    let (ranges, agreement) = shared_ranges(&shared);
+
+    ranges
+        .iter()
+        .map(|range| {
+            range.map(|(start, end)| MatchInterval {
+                start: start as f32 * frame_duration,
+                end: end as f32 * frame_duration,
+                peak: agreement,
+                frames: (end - start) as u32,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{longest_shared, majority_range, shared_ranges, SharedSegment};
+
+    fn noise(len: usize, seed: u32) -> Vec<u32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                state
+            })
+            .collect()
+    }
+
+    #[test]
+    fn longest_shared_finds_intro() {
+        let intro = noise(40, 7);
+        let mut a = noise(20, 1);
+        a.extend(&intro);
+        a.extend(noise(30, 2));
+        let mut b = noise(50, 3);
+        b.extend(&intro);
+        b.extend(noise(10, 4));
+
+        assert_eq!(
+            longest_shared(&a, &b, 10, 0.35),
+            Some(SharedSegment {
+                a_start: 20,
+                b_start: 50,
+                len: 40
+            })
+        );
+    }
+
+    #[test]
+    fn longest_shared_for_nothing() {
+        assert_eq!(longest_shared(&noise(40, 1), &noise(40, 2), 10, 0.35), None);
+        assert_eq!(longest_shared(&[], &noise(40, 2), 10, 0.35), None);
+    }
+
+    fn segment(a_start: usize, b_start: usize, len: usize) -> Option<SharedSegment> {
+        Some(SharedSegment {
+            a_start,
+            b_start,
+            len,
+        })
+    }
+
+    #[test]
+    fn shared_ranges_with_bad_first_file() {
+        // file 0 has no intro, files 1-3 share 100 frames at different positions
+        let shared = vec![
+            vec![None, None, None, None],
+            vec![None, None, segment(10, 30, 100), segment(10, 0, 100)],
+            vec![None, segment(30, 10, 100), None, segment(30, 0, 100)],
+            vec![None, segment(0, 10, 100), segment(0, 30, 100), None],
+        ];
+        let (ranges, agreement) = shared_ranges(&shared);
+
+        assert_eq!(
+            ranges,
+            vec![None, Some((10, 110)), Some((30, 130)), Some((0, 100))]
+        );
+        assert_eq!(agreement, 75.0);
+    }
+
+    #[test]
+    fn shared_ranges_for_nothing() {
+        let (ranges, agreement) = shared_ranges(&[vec![None, None], vec![None, None]]);
+
+        assert_eq!(ranges, vec![None, None]);
+        assert_eq!(agreement, 0.0);
+    }
+
+    #[test]
+    fn majority_range_of_ranges() {
+        assert_eq!(majority_range(&[], 1), None);
+        assert_eq!(
+            majority_range(&[(10, 50), (12, 48), (100, 200)], 2),
+            Some((12, 48))
+        );
+        assert_eq!(
+            majority_range(&[(10, 50), (12, 48), (100, 200)], 1),
+            Some((100, 200))
+        );
+    }
+}
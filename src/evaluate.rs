@@ -0,0 +1,157 @@
+use crate::tune::Label;
+
+/// Upper bounds (seconds) of the boundary error histogram buckets
+const ERROR_BUCKETS: [f32; 6] = [0.5, 1.0, 2.0, 5.0, 10.0, f32::INFINITY];
+
+/// Match/no-match outcomes of one side over the labeled files.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counts {
+    /// detected where labeled
+    pub true_positives: usize,
+    /// detected where not labeled
+    pub false_positives: usize,
+    /// not detected where labeled
+    pub false_negatives: usize,
+    /// not detected where not labeled
+    pub true_negatives: usize,
+}
+
+impl Counts {
+    pub fn precision(&self) -> Option<f32> {
+        let detected = self.true_positives + self.false_positives;
+        (detected > 0).then(|| self.true_positives as f32 / detected as f32)
+    }
+
+    pub fn recall(&self) -> Option<f32> {
+        let labeled = self.true_positives + self.false_negatives;
+        (labeled > 0).then(|| self.true_positives as f32 / labeled as f32)
+    }
+}
+
+pub fn count_matches(detected: &[Option<f32>], labels: &[Option<f32>]) -> Counts {
+    let mut counts = Counts::default();
+    for pair in detected.iter().zip(labels) {
+        match pair {
+            (Some(_), Some(_)) => counts.true_positives += 1,
+            (Some(_), None) => counts.false_positives += 1,
+            (None, Some(_)) => counts.false_negatives += 1,
+            (None, None) => counts.true_negatives += 1,
+        }
+    }
+
+    counts
+}
+
+/// Absolute boundary errors of files both detected and labeled.
+pub fn boundary_errors(detected: &[Option<f32>], labels: &[Option<f32>]) -> Vec<f32> {
+    detected
+        .iter()
+        .zip(labels)
+        .filter_map(|pair| match pair {
+            (Some(time), Some(label)) => Some((time - label).abs()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Number of errors in every `ERROR_BUCKETS` bucket.
+pub fn error_histogram(errors: &[f32]) -> Vec<usize> {
+    let mut histogram = vec![0; ERROR_BUCKETS.len()];
+    for error in errors {
+        if let Some(bucket) = ERROR_BUCKETS.iter().position(|&bound| *error < bound) {
+            histogram[bucket] += 1;
+        }
+    }
+
+    histogram
+}
+
+fn percent(value: Option<f32>) -> String {
+    match value {
+        Some(value) => format!("{:.1}%", value * 100.0),
+        None => "-".to_owned(),
+    }
+}
+
+fn print_side(name: &str, detected: &[Option<f32>], labels: &[Option<f32>]) {
+    let counts = count_matches(detected, labels);
+    let errors = boundary_errors(detected, labels);
+    println!(
+        "{name}: precision {}, recall {} ({} detected of {} labeled, {} false)",
+        percent(counts.precision()),
+        percent(counts.recall()),
+        counts.true_positives,
+        counts.true_positives + counts.false_negatives,
+        counts.false_positives
+    );
+    if !errors.is_empty() {
+        println!(
+            "  mean error {:.2}s, max error {:.2}s",
+            errors.iter().sum::<f32>() / errors.len() as f32,
+            errors.iter().copied().fold(0.0, f32::max)
+        );
+    }
+    let mut lower = 0.0;
+    for (bound, count) in ERROR_BUCKETS.iter().zip(error_histogram(&errors)) {
+        let bar = "#".repeat(count);
+        match bound.is_finite() {
+            true => println!("  {lower:>4}-{bound:<4}s {count:>4} {bar}"),
+            false => println!("  {:>9}s {count:>4} {bar}", format!(">={lower}")),
+        }
+        lower = *bound;
+    }
+}
+
+/// Prints precision/recall and error histogram for both sides.
+/// `detected` holds intro end and outro start for every label, `None` when not detected.
+pub fn print_report(labels: &[Label], detected: &[(Option<f32>, Option<f32>)]) {
+    println!("\nEvaluation of {} labeled files", labels.len());
+    print_side(
+        "Intro end",
+        &detected.iter().map(|times| times.0).collect::<Vec<_>>(),
+        &labels
+            .iter()
+            .map(|label| label.intro_end)
+            .collect::<Vec<_>>(),
+    );
+    print_side(
+        "Outro start",
+        &detected.iter().map(|times| times.1).collect::<Vec<_>>(),
+        &labels
+            .iter()
+            .map(|label| label.outro_start)
+            .collect::<Vec<_>>(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{boundary_errors, count_matches, error_histogram};
+
+    #[test]
+    fn count_matches_precision_recall() {
+        let counts = count_matches(
+            &[Some(10.0), Some(20.0), None, Some(5.0)],
+            &[Some(10.0), Some(21.0), Some(30.0), None],
+        );
+
+        assert_eq!(counts.true_positives, 2);
+        assert_eq!(counts.false_positives, 1);
+        assert_eq!(counts.false_negatives, 1);
+        assert_eq!(counts.true_negatives, 0);
+        assert_eq!(counts.precision(), Some(2.0 / 3.0));
+        assert_eq!(counts.recall(), Some(2.0 / 3.0));
+        assert_eq!(count_matches(&[None], &[None]).precision(), None);
+    }
+
+    #[test]
+    fn error_histogram_buckets() {
+        let errors = boundary_errors(
+            &[Some(10.2), Some(21.5), Some(40.0), None],
+            &[Some(10.0), Some(20.0), Some(60.0), Some(5.0)],
+        );
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(error_histogram(&errors), vec![1, 0, 1, 0, 0, 1]);
+    }
+}
@@ -0,0 +1,250 @@
+use std::f32::consts::PI;
+
+use crate::match_interval::MatchInterval;
+
+/// Audio is decoded as mono signed 16 bit PCM at this rate
+pub const SAMPLE_RATE: u32 = 8000;
+/// Samples per analysed frame (128 ms)
+pub const FRAME_SIZE: usize = 1024;
+/// Samples between frame starts (32 ms)
+pub const HOP_SIZE: usize = 256;
+/// Frequency bands, two neighbours give one bit so 33 bands give a 32 bit hash
+const BANDS: usize = 33;
+const MIN_FREQUENCY: f32 = 300.0;
+const MAX_FREQUENCY: f32 = 2000.0;
+
+/// In-place radix-2 FFT, `re` and `im` length must be a power of two.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Energy of logarithmically spaced bands for every frame.
+fn band_energies(samples: &[i16]) -> Vec<[f32; BANDS]> {
+    let bin_width = SAMPLE_RATE as f32 / FRAME_SIZE as f32;
+    let band_edges: Vec<usize> = (0..=BANDS)
+        .map(|b| {
+            let frequency =
+                MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(b as f32 / BANDS as f32);
+            (frequency / bin_width).round() as usize
+        })
+        .collect();
+    let window: Vec<f32> = (0..FRAME_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME_SIZE as f32).cos())
+        .collect();
+
+    let mut energies = Vec::new();
+    let mut re = vec![0.0; FRAME_SIZE];
+    let mut im = vec![0.0; FRAME_SIZE];
+    let mut start = 0;
+    while start + FRAME_SIZE <= samples.len() {
+        for i in 0..FRAME_SIZE {
+            re[i] = samples[start + i] as f32 * window[i];
+            im[i] = 0.0;
+        }
+        fft(&mut re, &mut im);
+
+        let mut frame_energies = [0.0; BANDS];
+        for (b, energy) in frame_energies.iter_mut().enumerate() {
+            for bin in band_edges[b]..band_edges[b + 1].max(band_edges[b] + 1) {
+                *energy += re[bin] * re[bin] + im[bin] * im[bin];
+            }
+        }
+        energies.push(frame_energies);
+        start += HOP_SIZE;
+    }
+
+    energies
+}
+
+/// 32 bit sub-fingerprint per frame: sign of the band energy difference change over time.
+pub fn fingerprint(samples: &[i16]) -> Vec<u32> {
+    let energies = band_energies(samples);
+
+    energies
+        .windows(2)
+        .map(|pair| {
+            let (previous, current) = (&pair[0], &pair[1]);
+            let mut hash: u32 = 0;
+            for b in 0..BANDS - 1 {
+                let difference = (current[b] - current[b + 1]) - (previous[b] - previous[b + 1]);
+                if difference > 0.0 {
+                    hash |= 1 << b;
+                }
+            }
+            hash
+        })
+        .collect()
+}
+
+/// Seconds between two fingerprint frames
+pub fn frame_duration() -> f32 {
+    HOP_SIZE as f32 / SAMPLE_RATE as f32
+}
+
+/// Share of differing bits between `needle` and `haystack` placed at `offset`.
+pub fn bit_error_rate(haystack: &[u32], needle: &[u32], offset: usize) -> f32 {
+    let errors: u32 = needle
+        .iter()
+        .zip(&haystack[offset..offset + needle.len()])
+        .map(|(a, b)| (a ^ b).count_ones())
+        .sum();
+
+    errors as f32 / (needle.len() * 32) as f32
+}
+
+/// Finds every place where `needle` matches `haystack` with bit error rate below `max_bit_error_rate`.
+/// Start times are limited by `greater_than`/`less_than` seconds (-1 means no limit).
+pub fn find_matches(
+    haystack: &[u32],
+    needle: &[u32],
+    max_bit_error_rate: f32,
+    greater_than: f32,
+    less_than: f32,
+) -> Vec<MatchInterval> {
+    let mut intervals: Vec<MatchInterval> = Vec::new();
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return intervals;
+    }
+
+    let frame_duration = frame_duration();
+    let needle_duration = needle.len() as f32 * frame_duration;
+    // offsets next to each other belong to the same match, keep the best one
+    let mut best: Option<(usize, f32)> = None;
+    for offset in 0..=haystack.len() - needle.len() {
+        let time = offset as f32 * frame_duration;
+        let in_window = (greater_than == -1.0 || time > greater_than)
+            && (less_than == -1.0 || less_than > time);
+        let bit_error_rate = match in_window {
+            true => bit_error_rate(haystack, needle, offset),
+            false => 1.0,
+        };
+        if bit_error_rate < max_bit_error_rate {
+            if best.is_none_or(|(_, best_rate)| bit_error_rate < best_rate) {
+                best = Some((offset, bit_error_rate));
+            }
+        } else if let Some((best_offset, best_rate)) = best.take() {
+            let start = best_offset as f32 * frame_duration;
+            intervals.push(MatchInterval {
+                start,
+                end: start + needle_duration,
+                peak: 100.0 * (1.0 - best_rate),
+                frames: needle.len() as u32,
+            });
+        }
+    }
+    if let Some((best_offset, best_rate)) = best {
+        let start = best_offset as f32 * frame_duration;
+        intervals.push(MatchInterval {
+            start,
+            end: start + needle_duration,
+            peak: 100.0 * (1.0 - best_rate),
+            frames: needle.len() as u32,
+        });
+    }
+
+    intervals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bit_error_rate, fft, find_matches, fingerprint, frame_duration, SAMPLE_RATE};
+
+    /// Deterministic noise-like signal
+    fn signal(len: usize, seed: u32) -> Vec<i16> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                ((state >> 16) as i16) / 4
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fft_of_impulse_is_flat() {
+        let mut re = vec![0.0; 8];
+        let mut im = vec![0.0; 8];
+        re[0] = 1.0;
+        fft(&mut re, &mut im);
+
+        for i in 0..8 {
+            assert!((re[i] - 1.0).abs() < 1e-6);
+            assert!(im[i].abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn fft_finds_sine_bin() {
+        let n = 64;
+        let mut re: Vec<f32> = (0..n)
+            .map(|i| (2.0 * std::f32::consts::PI * 4.0 * i as f32 / n as f32).sin())
+            .collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im);
+
+        let magnitudes: Vec<f32> = (0..n / 2)
+            .map(|i| (re[i] * re[i] + im[i] * im[i]).sqrt())
+            .collect();
+        let peak = magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0;
+        assert_eq!(peak, 4);
+    }
+
+    #[test]
+    fn find_matches_locates_clip() {
+        let rate = SAMPLE_RATE as usize;
+        let mut episode = signal(rate * 4, 1);
+        let clip = signal(rate * 2, 2);
+        // clip starts at 4 seconds
+        episode.extend(&clip);
+        episode.extend(signal(rate * 4, 3));
+
+        let haystack = fingerprint(&episode);
+        let needle = fingerprint(&clip);
+        assert_eq!(bit_error_rate(&needle, &needle, 0), 0.0);
+
+        let intervals = find_matches(&haystack, &needle, 0.35, -1.0, -1.0);
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].start - 4.0).abs() <= frame_duration());
+        assert!(intervals[0].peak > 90.0);
+
+        let outside_window = find_matches(&haystack, &needle, 0.35, 5.0, -1.0);
+        assert!(outside_window.is_empty());
+    }
+}
@@ -17,17 +17,57 @@ pub fn parse_float<'a>(str: &'a str, splitter: &'a str) -> PartsFloat<'a> {
     let after_str = parts.next().expect("Can not detect after splitter").trim();
     let after_float: f32 = after_str.parse().unwrap_or(0.0);
 
-    return PartsFloat {
+    PartsFloat {
         before_str,
         before_float,
         after_str,
         after_float,
+    }
+}
+
+/// Parses a time spec like `90`, `-0.5`, `1:30` or `01:01:30.5` into seconds.
+pub fn parse_time(str: &str) -> Option<f32> {
+    let str = str.trim();
+    let (sign, unsigned) = match str.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, str),
     };
+    if unsigned.is_empty() {
+        return None;
+    }
+
+    let mut seconds: f32 = 0.0;
+    let mut parts_count = 0;
+    for part in unsigned.split(':') {
+        parts_count += 1;
+        if parts_count > 3 {
+            return None;
+        }
+        let value: f32 = part.parse().ok()?;
+        if value < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+
+    Some(sign * seconds)
+}
+
+/// Finds `key` in an ffmpeg log line and parses the number right after it,
+/// e.g. `silence_end: 86.1 | silence_duration: 1.9` or `black_start:0 black_end:1.5`.
+pub fn parse_log_value(msg: &str, key: &str) -> Option<f32> {
+    let index = msg.find(key)?;
+    let value = msg[index + key.len()..]
+        .trim_start()
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '|')
+        .next()?;
+
+    value.parse().ok()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_float;
+    use super::{parse_float, parse_log_value, parse_time};
 
     #[test]
     fn parse_float_for_nothing() {
@@ -61,4 +101,40 @@ mod tests {
         assert_eq!(parsed.before_float, 45.56);
         assert_eq!(parsed.after_float, 5.2);
     }
+
+    #[test]
+    fn parse_time_for_seconds() {
+        assert_eq!(parse_time("90"), Some(90.0));
+        assert_eq!(parse_time("1.5"), Some(1.5));
+        assert_eq!(parse_time("-0.5"), Some(-0.5));
+    }
+
+    #[test]
+    fn parse_time_for_clock() {
+        assert_eq!(parse_time("1:30"), Some(90.0));
+        assert_eq!(parse_time("01:01:30.5"), Some(3690.5));
+        assert_eq!(parse_time("-0:02"), Some(-2.0));
+    }
+
+    #[test]
+    fn parse_time_for_invalid() {
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("-"), None);
+        assert_eq!(parse_time("1:-2"), None);
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time("abc"), None);
+    }
+
+    #[test]
+    fn parse_log_value_for_keys() {
+        let msg = "[silencedetect @ 0x55] [info] silence_end: 86.1 | silence_duration: 1.9";
+        assert_eq!(parse_log_value(msg, "silence_end:"), Some(86.1));
+        assert_eq!(parse_log_value(msg, "silence_duration:"), Some(1.9));
+        assert_eq!(parse_log_value(msg, "silence_start:"), None);
+        assert_eq!(
+            parse_log_value("black_start:0 black_end:1.5", "black_end:"),
+            Some(1.5)
+        );
+        assert_eq!(parse_log_value("key: abc", "key:"), None);
+    }
 }
@@ -0,0 +1,101 @@
+use crate::{match_interval::MatchInterval, tune::median};
+
+/// Matches further from the median start than this many median absolute deviations
+/// (or `margin` if more) are false matches
+const MAX_DEVIATIONS: f32 = 3.0;
+
+/// Highest scored interval, longer one on equal score.
+pub fn best_interval(intervals: &[MatchInterval]) -> Option<MatchInterval> {
+    intervals
+        .iter()
+        .max_by(|a, b| {
+            a.peak
+                .total_cmp(&b.peak)
+                .then((a.end - a.start).total_cmp(&(b.end - b.start)))
+        })
+        .copied()
+}
+
+/// Intervals starting near the median start, a false match in one file is dropped.
+pub fn inliers(intervals: &[MatchInterval], margin: f32) -> Vec<MatchInterval> {
+    let starts: Vec<f32> = intervals.iter().map(|i| i.start).collect();
+    let Some(center) = median(&starts) else {
+        return Vec::new();
+    };
+    let deviations: Vec<f32> = starts.iter().map(|start| (start - center).abs()).collect();
+    let spread = median(&deviations).unwrap_or(0.0);
+    let max_distance = (MAX_DEVIATIONS * spread).max(margin);
+
+    intervals
+        .iter()
+        .filter(|i| (i.start - center).abs() <= max_distance)
+        .copied()
+        .collect()
+}
+
+/// Whole seconds window around every interval widened by `margin`, start is never negative.
+pub fn infer_window(intervals: &[MatchInterval], margin: f32) -> Option<(f32, f32)> {
+    let start = intervals.iter().map(|i| i.start).reduce(f32::min)?;
+    let end = intervals.iter().map(|i| i.end).reduce(f32::max)?;
+
+    Some(((start - margin).floor().max(0.0), (end + margin).ceil()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{best_interval, infer_window, inliers};
+    use crate::match_interval::MatchInterval;
+
+    fn interval(start: f32, end: f32, peak: f32) -> MatchInterval {
+        MatchInterval {
+            peak,
+            ..MatchInterval::new(start, end)
+        }
+    }
+
+    #[test]
+    fn best_interval_by_score_then_length() {
+        assert_eq!(best_interval(&[]), None);
+        assert_eq!(
+            best_interval(&[
+                interval(10.0, 11.0, 90.0),
+                interval(60.0, 64.0, 98.0),
+                interval(300.0, 301.0, 98.0)
+            ]),
+            Some(interval(60.0, 64.0, 98.0))
+        );
+    }
+
+    #[test]
+    fn infer_window_with_margin() {
+        assert_eq!(infer_window(&[], 10.0), None);
+        assert_eq!(
+            infer_window(
+                &[interval(62.5, 64.0, 0.0), interval(70.2, 71.5, 0.0)],
+                10.0
+            ),
+            Some((52.0, 82.0))
+        );
+        assert_eq!(
+            infer_window(&[interval(5.0, 7.0, 0.0)], 10.0),
+            Some((0.0, 17.0))
+        );
+    }
+
+    #[test]
+    fn infer_window_drops_outliers() {
+        let intervals = [
+            interval(60.0, 61.5, 0.0),
+            interval(61.0, 62.5, 0.0),
+            interval(62.0, 63.5, 0.0),
+            interval(63.0, 64.5, 0.0),
+            // false match in the middle of one file
+            interval(900.0, 901.0, 0.0),
+        ];
+
+        let kept = inliers(&intervals, 10.0);
+        assert_eq!(kept.len(), 4);
+        assert_eq!(infer_window(&kept, 10.0), Some((50.0, 75.0)));
+        assert!(inliers(&[], 10.0).is_empty());
+    }
+}
@@ -1,20 +1,117 @@
+use std::io::{stdin, stdout, Write};
 use std::path::{Path, PathBuf};
 use std::{env, fs};
 
-use calc_duration::calc_duration;
-use clap::Parser;
-use detect_frame::detect_frame;
+use cache::{CacheMode, FileCache, CACHE_EXTENSION};
+use clap::{Parser, Subcommand};
+use config::{read_config_args, CONFIG_FILENAME};
+use cut::{allow_fallback, AfterOutro, Cut, CutSource, OnNoMatch};
+use detect_ads::{break_markers, group_ad_blocks};
+use detect_audio::AudioReference;
+use detect_black::{detect_black_scenes, BlackScenes};
+use detect_credits::credits_start;
+use detect_frame::{min_score, score_intervals, FrameReference, MatchMetric};
+use detect_junk::{lead_in_end, trailing_start};
+use detect_recap::{classify_frames, hash_frames, recap_cuts, FrameKind, HashedFrame};
+use detect_silence::detect_silence;
+use detect_subtitles::{detect_subtitle_gaps, gap_boundaries};
+use detector::{
+    cached_audio_matches, cached_frame_scores, parse_detectors_spec, AudioDetector, BlackDetector,
+    ChapterDetector, CreditsDetector, Detector, FrameDetector, JunkDetector, ManualDetector, Side,
+    SilenceDetector, SubtitleDetector, VotingDetector,
+};
+use discover::discover_intros;
+use evaluate::print_report;
+use helpers::parse_time;
+use infer_windows::{best_interval, infer_window, inliers};
 use make_screenshot::make_screenshot;
-use trim_start_end::trim_start_end;
+use match_interval::MatchInterval;
+use probe_media::probe_media;
+use refs::RefLibrary;
+use snap::Snapper;
+use timeline::{peaks, thumbnail, timeline_filepath, write_timeline, TimelineSeries};
+use trim_start_end::{trim_start_end, TrimOptions};
+use tune::{parse_labels, tune, Label, TuneSide};
 
-mod calc_duration;
+mod cache;
+mod config;
+mod cut;
+mod decode_frames;
+mod detect_ads;
+mod detect_audio;
+mod detect_black;
+mod detect_chapters;
+mod detect_credits;
 mod detect_frame;
+mod detect_junk;
+mod detect_recap;
+mod detect_silence;
+mod detect_subtitles;
+mod detector;
+mod discover;
+mod evaluate;
+mod fingerprint;
 mod helpers;
+mod infer_windows;
 mod make_screenshot;
+mod match_interval;
+mod perceptual_hash;
+mod probe_media;
+mod refs;
+mod snap;
+mod timeline;
 mod trim_start_end;
+mod tune;
+
+/// Junk segments closer than this (in seconds) are chained together
+const JUNK_GAP: f32 = 1.0;
+/// Silences closer than this (in seconds) are chained together
+const SILENCE_GAP: f32 = 0.5;
+/// Discovered intro starting later than this (in seconds) follows a cold open
+const DISCOVER_LEAD_IN: f32 = 1.0;
+/// Detectors finding exact content boundaries, intro and outro offsets are not added to them
+const BOUNDARY_DETECTORS: [&str; 4] = ["silence", "junk", "credits", "discover"];
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Find --if/--of match parameters with the lowest error against labeled boundaries
+    Tune {
+        #[clap(help = "CSV file with file,intro_end,outro_start lines")]
+        labels: String,
+    },
+    /// Run configured detectors over labeled files and report precision/recall and boundary errors
+    Evaluate {
+        #[clap(help = "CSV file with file,intro_end,outro_start lines")]
+        labels: String,
+    },
+    /// Manage named reference frames used as --if/--of show/name
+    Refs {
+        #[clap(subcommand)]
+        command: RefsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RefsCommand {
+    /// Grab the frame at given time of a video (relative to --dir) into the library
+    Add {
+        show: String,
+        name: String,
+        video: String,
+        #[clap(help = "frame time, e.g. 00:01:23 or 83.5")]
+        time: String,
+    },
+    /// Print references with their source, time and resolution
+    List {
+        #[clap(default_value = "")]
+        show: String,
+    },
+    /// Remove a reference from the library
+    Delete { show: String, name: String },
+}
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, args_override_self = true)]
 struct Args {
     #[clap(
         short = 'd',
@@ -24,6 +121,27 @@ struct Args {
     )]
     dir: String,
 
+    #[clap(
+        long = "config",
+        help = "config file path (ffmpegtrim.ini inside input directory is used when exists)",
+        default_value = ""
+    )]
+    config: String,
+
+    #[clap(
+        long = "refs",
+        help = "reference library directory (~/.ffmpegtrim/refs when empty)",
+        default_value = ""
+    )]
+    refs: String,
+
+    #[clap(
+        long = "show",
+        help = "config section with show specific options",
+        default_value = ""
+    )]
+    show: String,
+
     #[clap(
         long = "ts",
         help = "trim seconds from start of file",
@@ -31,9 +149,20 @@ struct Args {
     )]
     trim_start: String,
 
-    #[clap(long = "if", help = "intro frame file path", default_value = "")]
+    #[clap(
+        long = "if",
+        help = "intro frame file path or library reference, e.g. myshow/intro",
+        default_value = ""
+    )]
     intro_frame: String,
 
+    #[clap(
+        long = "intro-audio",
+        help = "intro theme audio clip file path",
+        default_value = ""
+    )]
+    intro_audio: String,
+
     #[clap(
         long = "ifgt",
         help = "intro frame greater than duration",
@@ -50,18 +179,33 @@ struct Args {
 
     #[clap(
         long = "ifba",
-        help = "intro frame blackframe amount",
+        help = "intro frame blackframe amount (blackframe metric only)",
         default_value = "95"
     )]
     intro_blackframe_amount: String,
 
     #[clap(
         long = "ifbt",
-        help = "intro frame blackframe threshold",
+        help = "intro frame blackframe threshold (blackframe metric only)",
         default_value = "15"
     )]
     intro_blackframe_threshold: String,
 
+    #[clap(
+        long = "ifmt",
+        help = "intro frame match threshold: maximum hash distance (0..64, default 16), minimum ssim (0..1, default 0.9) or minimum psnr (dB, default 30)",
+        default_value = ""
+    )]
+    intro_match_threshold: String,
+
+    #[clap(
+        long = "intro-offset",
+        help = "time added to detected last intro frame or audio match, e.g. -0.5 or 0:01",
+        default_value = "0",
+        allow_hyphen_values = true
+    )]
+    intro_offset: String,
+
     #[clap(
         long = "te",
         help = "trim seconds from end of file",
@@ -69,9 +213,20 @@ struct Args {
     )]
     trim_end: String,
 
-    #[clap(long = "of", help = "outro frame file path", default_value = "")]
+    #[clap(
+        long = "of",
+        help = "outro frame file path or library reference, e.g. myshow/outro",
+        default_value = ""
+    )]
     outro_frame: String,
 
+    #[clap(
+        long = "outro-audio",
+        help = "outro theme audio clip file path",
+        default_value = ""
+    )]
+    outro_audio: String,
+
     #[clap(
         long = "ofgt",
         help = "outro frame greater than duration",
@@ -88,21 +243,290 @@ struct Args {
 
     #[clap(
         long = "ofba",
-        help = "outro frame blackframe amount",
+        help = "outro frame blackframe amount (blackframe metric only)",
         default_value = "95"
     )]
     outro_blackframe_amount: String,
 
     #[clap(
         long = "ofbt",
-        help = "outro frame blackframe threshold",
+        help = "outro frame blackframe threshold (blackframe metric only)",
         default_value = "15"
     )]
     outro_blackframe_threshold: String,
 
+    #[clap(
+        long = "ofmt",
+        help = "outro frame match threshold: maximum hash distance (0..64, default 16), minimum ssim (0..1, default 0.9) or minimum psnr (dB, default 30)",
+        default_value = ""
+    )]
+    outro_match_threshold: String,
+
+    #[clap(
+        long = "discover",
+        help = "find intro shared by most files (same series) by audio, no reference needed"
+    )]
+    discover: bool,
+
+    #[clap(
+        long = "discover-minutes",
+        help = "minutes from start of every file searched by discover",
+        default_value = "5"
+    )]
+    discover_minutes: String,
+
+    #[clap(
+        long = "detect-junk",
+        help = "cut lead-in and trailing color bars, solid color and frozen frames"
+    )]
+    detect_junk: bool,
+
+    #[clap(
+        long = "detect-credits",
+        help = "cut outro where scrolling credits on dark background start"
+    )]
+    detect_credits: bool,
+
+    #[clap(
+        long = "trim-silence",
+        help = "cut leading and trailing silence, e.g. of podcasts and audiobooks (see --silence-noise/--silence-duration)"
+    )]
+    trim_silence: bool,
+
+    #[clap(
+        long = "intro-detectors",
+        help = "vote for intro end with weighted detectors, e.g. frame:2,audio,silence:0.5 (frame, audio, silence, black, chapter, subtitles, manual)",
+        default_value = ""
+    )]
+    intro_detectors: String,
+
+    #[clap(
+        long = "outro-detectors",
+        help = "vote for outro start with weighted detectors, e.g. frame:2,black:0.5 (frame, audio, silence, black, chapter, subtitles, credits, manual)",
+        default_value = ""
+    )]
+    outro_detectors: String,
+
+    #[clap(
+        long = "infer-windows",
+        help = "detect intro/outro without windows on this many first files and use windows around the matches for all files",
+        default_value = "0"
+    )]
+    infer_windows: String,
+
+    #[clap(
+        long = "window-margin",
+        help = "seconds added on both sides of inferred windows",
+        default_value = "10"
+    )]
+    window_margin: String,
+
+    #[clap(
+        long = "vote-tolerance",
+        help = "seconds between detector results that still agree",
+        default_value = "2"
+    )]
+    vote_tolerance: String,
+
+    #[clap(
+        long = "intro-chapters",
+        help = "chapter title parts used by chapter detector for intro",
+        default_value = "intro,opening"
+    )]
+    intro_chapters: String,
+
+    #[clap(
+        long = "outro-chapters",
+        help = "chapter title parts used by chapter detector for outro",
+        default_value = "outro,ending,credits"
+    )]
+    outro_chapters: String,
+
+    #[clap(
+        long = "audio-match",
+        help = "maximum bit error rate (0..1) of intro/outro audio fingerprint match",
+        default_value = "0.35"
+    )]
+    audio_match: String,
+
+    #[clap(
+        long = "match-metric",
+        help = "how frames are compared with --if/--of reference",
+        value_enum,
+        default_value_t = MatchMetric::Hash
+    )]
+    match_metric: MatchMetric,
+
+    #[clap(
+        long = "sample-rate",
+        help = "frames per second compared with hash, ssim and psnr metrics",
+        default_value = "2"
+    )]
+    sample_rate: String,
+
+    #[clap(
+        long = "outro-offset",
+        help = "time added to detected first outro frame or audio match, e.g. 1 or -0:00.5",
+        default_value = "1",
+        allow_hyphen_values = true
+    )]
+    outro_offset: String,
+
+    #[clap(
+        long = "snap-silence",
+        help = "move trim points to the nearest silence within given seconds",
+        default_value = ""
+    )]
+    snap_silence: String,
+
+    #[clap(
+        long = "silence-noise",
+        help = "silencedetect noise tolerance, e.g. -30dB or 0.001",
+        default_value = "-30dB",
+        allow_hyphen_values = true
+    )]
+    silence_noise: String,
+
+    #[clap(
+        long = "silence-duration",
+        help = "silencedetect minimal silence duration in seconds",
+        default_value = "0.5"
+    )]
+    silence_duration: String,
+
+    #[clap(
+        long = "snap-black",
+        help = "move trim points to the nearest black segment within given seconds",
+        default_value = ""
+    )]
+    snap_black: String,
+
+    #[clap(
+        long = "black-duration",
+        help = "blackdetect minimal black duration in seconds",
+        default_value = "0.1"
+    )]
+    black_duration: String,
+
+    #[clap(
+        long = "black-threshold",
+        help = "blackdetect pixel threshold (0..1)",
+        default_value = "0.10"
+    )]
+    black_threshold: String,
+
+    #[clap(
+        long = "snap-scene",
+        help = "move trim points to the nearest hard scene change within given seconds",
+        default_value = ""
+    )]
+    snap_scene: String,
+
+    #[clap(
+        long = "snap-subtitles",
+        help = "move intro end to the nearest end and outro start to the nearest start of a long subtitle gap within given seconds",
+        default_value = ""
+    )]
+    snap_subtitles: String,
+
+    #[clap(
+        long = "subtitle-gap",
+        help = "seconds without subtitle lines counted as a gap (first subtitle stream)",
+        default_value = "10"
+    )]
+    subtitle_gap: String,
+
+    #[clap(
+        long = "scene-threshold",
+        help = "scdet scene change threshold (0..100)",
+        default_value = "10"
+    )]
+    scene_threshold: String,
+
+    #[clap(
+        long = "remove-ads",
+        help = "remove ad blocks found where black frames and silence overlap (requires encoding)"
+    )]
+    remove_ads: bool,
+
+    #[clap(
+        long = "confirm-ads",
+        help = "ask before removing the printed ad blocks of every file"
+    )]
+    confirm_ads: bool,
+
+    #[clap(
+        long = "remove-recap",
+        help = "remove content before intro end that is also in the previous file, keeping unique cold open (requires encoding)"
+    )]
+    remove_recap: bool,
+
+    #[clap(
+        long = "ad-min",
+        help = "minimal ad block length in seconds",
+        default_value = "60"
+    )]
+    ad_min: String,
+
+    #[clap(
+        long = "ad-max",
+        help = "maximal ad block length in seconds",
+        default_value = "600"
+    )]
+    ad_max: String,
+
+    #[clap(
+        long = "on-no-match",
+        help = "what to do with a file when intro/outro frame is not matched",
+        value_enum,
+        default_value_t = OnNoMatch::Fallback
+    )]
+    on_no_match: OnNoMatch,
+
+    #[clap(
+        long = "after-outro",
+        help = "what to do with content after detected outro end, e.g. next episode preview (keep requires encoding and an audio or credits outro match)",
+        value_enum,
+        default_value_t = AfterOutro::Drop
+    )]
+    after_outro: AfterOutro,
+
+    #[clap(
+        long = "no-cache",
+        help = "neither read nor write probe and detection results in <file>.ffmpegtrim.json"
+    )]
+    no_cache: bool,
+
+    #[clap(
+        long = "refresh-cache",
+        help = "detect again and overwrite cached probe and detection results"
+    )]
+    refresh_cache: bool,
+
     #[clap(long = "se", help = "skip encoding")]
     skip_encoding: bool,
 
+    #[clap(
+        long = "fade-in",
+        help = "seconds of fade in at the start of output (requires encoding)",
+        default_value = ""
+    )]
+    fade_in: String,
+
+    #[clap(
+        long = "fade-out",
+        help = "seconds of fade out at the end of output (requires encoding)",
+        default_value = ""
+    )]
+    fade_out: String,
+
+    #[clap(
+        long = "join-dip",
+        help = "seconds of dip to black and silence around every join of removed segments, not a crossfade (requires encoding)",
+        default_value = ""
+    )]
+    join_dip: String,
+
     #[clap(
         long = "mv",
         help = "take video stream by index from input source and map to the output (skip anything else, e.g. subtitles)",
@@ -148,19 +572,355 @@ struct Args {
 
     #[clap(long = "scr", help = "make screenshot at time", default_value = "")]
     make_screenshot: String,
+
+    #[clap(
+        long = "debug-timeline",
+        help = "write --if/--of frame scores over time to SVG (or .html) file, one per input file",
+        default_value = ""
+    )]
+    debug_timeline: String,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+/// Builds detector `name` for one side from its command line options.
+fn build_detector(
+    args: &Args,
+    side: Side,
+    name: &str,
+    frame_filepath: Option<&str>,
+    audio_filepath: Option<&str>,
+) -> Box<dyn Detector> {
+    let (gt, lt, amount, threshold, match_threshold, chapters, manual) = match side {
+        Side::Intro => (
+            &args.intro_gt_duration,
+            &args.intro_lt_duration,
+            &args.intro_blackframe_amount,
+            &args.intro_blackframe_threshold,
+            &args.intro_match_threshold,
+            &args.intro_chapters,
+            parse_time(&args.trim_start),
+        ),
+        Side::Outro => (
+            &args.outro_gt_duration,
+            &args.outro_lt_duration,
+            &args.outro_blackframe_amount,
+            &args.outro_blackframe_threshold,
+            &args.outro_match_threshold,
+            &args.outro_chapters,
+            // --te counts from the end
+            parse_time(&args.trim_end).map(|time| -time),
+        ),
+    };
+
+    match name {
+        "frame" => Box::new(FrameDetector {
+            frame_filepath: frame_filepath
+                .expect("Frame detector requires --if/--of")
+                .to_owned(),
+            blackframe_amount: amount.to_owned(),
+            blackframe_threshold: threshold.to_owned(),
+            metric: args.match_metric,
+            match_threshold: match_threshold.to_owned(),
+            sample_rate: args.sample_rate.to_owned(),
+            greater_than_duration: gt.to_owned(),
+            less_than_duration: lt.to_owned(),
+        }),
+        "audio" => Box::new(AudioDetector {
+            clip_filepath: audio_filepath
+                .expect("Audio detector requires --intro-audio/--outro-audio")
+                .to_owned(),
+            max_bit_error_rate: args.audio_match.to_owned(),
+            greater_than_duration: gt.to_owned(),
+            less_than_duration: lt.to_owned(),
+        }),
+        "silence" => Box::new(SilenceDetector {
+            noise: args.silence_noise.to_owned(),
+            silence_duration: args.silence_duration.to_owned(),
+        }),
+        "black" => Box::new(BlackDetector {
+            black_duration: args.black_duration.to_owned(),
+            black_threshold: args.black_threshold.to_owned(),
+        }),
+        "chapter" => Box::new(ChapterDetector {
+            titles: chapters.split(',').map(|t| t.trim().to_owned()).collect(),
+        }),
+        "subtitles" => Box::new(SubtitleDetector {
+            min_gap: parse_time(&args.subtitle_gap).expect("Unable to parse subtitle gap"),
+        }),
+        "credits" => Box::new(CreditsDetector),
+        "manual" => Box::new(ManualDetector {
+            time: manual.expect("Manual detector requires --ts/--te in seconds"),
+        }),
+        _ => panic!("Unknown detector {name}"),
+    }
+}
+
+/// Builds voting detector for one side from --intro-detectors/--outro-detectors.
+fn build_voting(
+    args: &Args,
+    side: Side,
+    frame_filepath: Option<&str>,
+    audio_filepath: Option<&str>,
+) -> Option<VotingDetector> {
+    let (spec, gt, lt) = match side {
+        Side::Intro => (
+            &args.intro_detectors,
+            &args.intro_gt_duration,
+            &args.intro_lt_duration,
+        ),
+        Side::Outro => (
+            &args.outro_detectors,
+            &args.outro_gt_duration,
+            &args.outro_lt_duration,
+        ),
+    };
+    let specs = parse_detectors_spec(spec);
+    if specs.is_empty() {
+        return None;
+    }
+
+    let detectors: Vec<(Box<dyn Detector>, f32)> = specs
+        .into_iter()
+        .map(|(name, weight)| {
+            let detector = build_detector(args, side, &name, frame_filepath, audio_filepath);
+            (detector, weight)
+        })
+        .collect();
+
+    Some(VotingDetector {
+        detectors,
+        side,
+        tolerance: parse_time(&args.vote_tolerance).expect("Unable to parse vote tolerance"),
+        greater_than: gt.parse().unwrap_or(-1.0),
+        less_than: lt.parse().unwrap_or(-1.0),
+    })
+}
+
+/// Detects intro/outro without windows on `file_pathes` and returns windows around the best matches.
+/// Frame references are used when given, audio clips otherwise.
+fn infer_windows(
+    args: &Args,
+    file_pathes: &[(String, String)],
+    frame_filepaths: [Option<&str>; 2],
+    audio_filepaths: [Option<&str>; 2],
+    cache_mode: CacheMode,
+) -> [Option<(f32, f32)>; 2] {
+    let margin = parse_time(&args.window_margin).expect("Unable to parse window margin");
+    let sample_rate: f32 = args
+        .sample_rate
+        .parse()
+        .expect("Unable to parse frame sample rate");
+    let sides = [
+        (
+            &args.intro_blackframe_amount,
+            &args.intro_blackframe_threshold,
+            &args.intro_match_threshold,
+        ),
+        (
+            &args.outro_blackframe_amount,
+            &args.outro_blackframe_threshold,
+            &args.outro_match_threshold,
+        ),
+    ];
+    let mut best: [Vec<(&str, Option<MatchInterval>)>; 2] = [Vec::new(), Vec::new()];
+
+    for (file_name, file_path) in file_pathes {
+        println!("Infer windows from {} =>", file_name);
+        let mut cache = FileCache::open(file_path, cache_mode);
+        let mut frame_sides: Vec<usize> = Vec::new();
+        let mut references: Vec<FrameReference> = Vec::new();
+        for (side, frame_filepath) in frame_filepaths.iter().enumerate() {
+            if let Some(frame_filepath) = frame_filepath {
+                let (amount, threshold, match_threshold) = sides[side];
+                frame_sides.push(side);
+                references.push(FrameReference {
+                    frame_filepath,
+                    match_threshold,
+                    blackframe_amount: amount,
+                    blackframe_threshold: threshold,
+                    greater_than_duration: "",
+                    less_than_duration: "",
+                });
+            }
+        }
+        if !references.is_empty() {
+            let scores = cached_frame_scores(
+                &mut cache,
+                file_path,
+                &references,
+                args.match_metric,
+                sample_rate,
+            );
+            for ((side, reference), reference_scores) in
+                frame_sides.iter().zip(&references).zip(&scores)
+            {
+                let intervals =
+                    score_intervals(reference_scores, reference, args.match_metric, sample_rate);
+                best[*side].push((file_name, best_interval(&intervals)));
+            }
+        }
+
+        let mut audio_sides: Vec<usize> = Vec::new();
+        let mut audio_references: Vec<AudioReference> = Vec::new();
+        for (side, clip_filepath) in audio_filepaths.iter().enumerate() {
+            if let Some(clip_filepath) = clip_filepath.filter(|_| frame_filepaths[side].is_none()) {
+                audio_sides.push(side);
+                audio_references.push(AudioReference {
+                    clip_filepath,
+                    greater_than_duration: "",
+                    less_than_duration: "",
+                });
+            }
+        }
+        if !audio_references.is_empty() {
+            let detected =
+                cached_audio_matches(&mut cache, file_path, &audio_references, &args.audio_match);
+            for (side, intervals) in audio_sides.iter().zip(&detected) {
+                best[*side].push((file_name, best_interval(intervals)));
+            }
+        }
+    }
+    let mut windows: [Option<(f32, f32)>; 2] = [None, None];
+    for (side, name) in ["intro", "outro"].iter().enumerate() {
+        let matched: Vec<MatchInterval> = best[side].iter().filter_map(|(_, m)| *m).collect();
+        let kept = inliers(&matched, margin);
+        for (file_name, interval) in &best[side] {
+            match interval {
+                None => println!("No {} match in {}", name, file_name),
+                Some(interval) if !kept.contains(interval) => println!(
+                    "Ignoring {} match {:?} in {}, far from other files",
+                    name, interval, file_name
+                ),
+                Some(_) => {}
+            }
+        }
+        if !best[side].is_empty() {
+            println!("Best {} matches {:?}", name, kept);
+        }
+        windows[side] = infer_window(&kept, margin);
+    }
+
+    windows
 }
 
 fn main() {
     let args = Args::parse();
+    let config_args = read_config_args(&args.config, &args.dir, &args.show);
+    let mut args = match config_args.is_empty() {
+        true => args,
+        // command line options go last to override config values
+        false => Args::parse_from(
+            env::args()
+                .take(1)
+                .chain(config_args)
+                .chain(env::args().skip(1)),
+        ),
+    };
+
+    if args.skip_encoding {
+        // segments are removed and faded with filters, checked before any detection runs
+        let removing = [
+            (args.remove_ads, "--remove-ads"),
+            (args.remove_recap, "--remove-recap"),
+            (args.after_outro == AfterOutro::Keep, "--after-outro keep"),
+            (!args.fade_in.is_empty(), "--fade-in"),
+            (!args.fade_out.is_empty(), "--fade-out"),
+            (!args.join_dip.is_empty(), "--join-dip"),
+        ];
+        if let Some((_, option)) = removing.iter().find(|(enabled, _)| *enabled) {
+            panic!("{option} requires encoding, skip encoding is not supported");
+        }
+    }
 
     let dir_path = Path::new(&args.dir);
-    if dir_path.is_dir() != true {
+    if !dir_path.is_dir() {
         panic!(
             "Invalid dir option provided! {} is not existing directory!",
             args.dir
         );
     }
 
+    let library = RefLibrary::open(&args.refs);
+    if let Some(Command::Refs { command }) = &args.command {
+        match command {
+            RefsCommand::Add {
+                show,
+                name,
+                video,
+                time,
+            } => {
+                let video_filepath = dir_path.join(video);
+                let meta = library.add(
+                    show,
+                    name,
+                    video_filepath.to_str().expect("Unable to get file path"),
+                    time,
+                );
+                println!("Added {show}/{name} {:?}", meta);
+            }
+            RefsCommand::List { show } => {
+                for (name, meta) in library.list(show) {
+                    match meta {
+                        Some(meta) => println!(
+                            "{name}\t{}x{}\t{}s\t{}",
+                            meta.width, meta.height, meta.time, meta.source
+                        ),
+                        None => println!("{name}"),
+                    }
+                }
+            }
+            RefsCommand::Delete { show, name } => match library.delete(show, name) {
+                true => println!("Deleted {show}/{name}"),
+                false => println!("No reference {show}/{name} in {:?}", library.dir),
+            },
+        }
+        return;
+    }
+    // existing files inside --dir win over library references
+    let reference_path = |reference: &str| -> PathBuf {
+        let path = dir_path.join(reference);
+        match path.exists() {
+            true => path,
+            false => library.resolve(reference).unwrap_or(path),
+        }
+    };
+
+    if let Some(Command::Tune { labels }) = &args.command {
+        let mut sides: Vec<TuneSide> = Vec::new();
+        let intro_frame_filepath = reference_path(&args.intro_frame);
+        let outro_frame_filepath = reference_path(&args.outro_frame);
+        if !args.intro_frame.is_empty() {
+            sides.push(TuneSide {
+                side: Side::Intro,
+                frame_filepath: intro_frame_filepath
+                    .to_str()
+                    .expect("Unable to get file path"),
+                greater_than_duration: &args.intro_gt_duration,
+                less_than_duration: &args.intro_lt_duration,
+            });
+        }
+        if !args.outro_frame.is_empty() {
+            sides.push(TuneSide {
+                side: Side::Outro,
+                frame_filepath: outro_frame_filepath
+                    .to_str()
+                    .expect("Unable to get file path"),
+                greater_than_duration: &args.outro_gt_duration,
+                less_than_duration: &args.outro_lt_duration,
+            });
+        }
+        tune(
+            &args.dir,
+            labels,
+            &sides,
+            args.match_metric,
+            &args.sample_rate,
+        );
+        return;
+    }
+
     let entries = fs::read_dir(dir_path).expect("Unable to read input ");
 
     let mut file_pathes: Vec<(String, String)> = Vec::new();
@@ -175,6 +935,7 @@ fn main() {
                 println!("raw {:?}", path);
             }
             if path_str.ends_with(&args.ext)
+                && !path_str.ends_with(CACHE_EXTENSION)
                 && (args.filter.is_empty() || path_str.contains(&args.filter))
             {
                 file_pathes.push((file_name_str.to_owned(), path_str.to_owned()));
@@ -182,7 +943,37 @@ fn main() {
         }
     }
 
-    if file_pathes.len() == 0 {
+    // labeled files are detected only, nothing is encoded
+    let mut evaluate_labels: Option<Vec<Label>> = match &args.command {
+        Some(Command::Evaluate { labels }) => Some(parse_labels(
+            &fs::read_to_string(labels).expect("Unable to read labels file"),
+        )),
+        _ => None,
+    };
+    if let Some(labels) = &mut evaluate_labels {
+        file_pathes.retain(|(file_name, _)| labels.iter().any(|label| &label.file == file_name));
+        // labels of files excluded by --ext/--filter or missing from --dir are not misses
+        let (processed, unprocessed): (Vec<Label>, Vec<Label>) =
+            labels.drain(..).partition(|label| {
+                file_pathes
+                    .iter()
+                    .any(|(file_name, _)| file_name == &label.file)
+            });
+        for label in &unprocessed {
+            println!(
+                "Labeled file {} not processed, left out of evaluation",
+                label.file
+            );
+        }
+        *labels = processed;
+    }
+    let mut evaluated: Vec<(String, Option<f32>, Option<f32>)> = Vec::new();
+
+    // discover and recap expect episodes of a series in file name order
+    if args.discover || args.remove_recap {
+        file_pathes.sort();
+    }
+    if file_pathes.is_empty() {
         println!("No files to process!");
         return;
     }
@@ -190,49 +981,632 @@ fn main() {
         println!("filtered {:?}", file_pathes);
     }
 
-    let intro_add: f32 = 0.0;
-    let outro_add: f32 = 1.0;
+    let intro_offset = parse_time(&args.intro_offset).expect("Unable to parse intro offset");
+    let outro_offset = parse_time(&args.outro_offset).expect("Unable to parse outro offset");
+    let fade_in = match args.fade_in.is_empty() {
+        true => 0.0,
+        false => parse_time(&args.fade_in).expect("Unable to parse fade in"),
+    };
+    let fade_out = match args.fade_out.is_empty() {
+        true => 0.0,
+        false => parse_time(&args.fade_out).expect("Unable to parse fade out"),
+    };
+    let join_dip = match args.join_dip.is_empty() {
+        true => 0.0,
+        false => parse_time(&args.join_dip).expect("Unable to parse join dip"),
+    };
 
-    for (file_name, file_path) in file_pathes {
-        if !args.make_screenshot.is_empty() {
+    let reference_filepath = |reference: &str| -> Option<String> {
+        if reference.is_empty() {
+            return None;
+        }
+        reference_path(reference)
+            .to_str()
+            .map(|path| path.to_owned())
+    };
+    let intro_frame_filepath = reference_filepath(&args.intro_frame);
+    let outro_frame_filepath = reference_filepath(&args.outro_frame);
+    let intro_audio_filepath = reference_filepath(&args.intro_audio);
+    let outro_audio_filepath = reference_filepath(&args.outro_audio);
+    let cache_mode = match (args.no_cache, args.refresh_cache) {
+        (true, _) => CacheMode::Off,
+        (false, true) => CacheMode::Refresh,
+        (false, false) => CacheMode::Use,
+    };
+
+    let infer_count: usize = args
+        .infer_windows
+        .parse()
+        .expect("Unable to parse infer windows files count");
+    if infer_count > 0 {
+        let [intro_window, outro_window] = infer_windows(
+            &args,
+            &file_pathes[..infer_count.min(file_pathes.len())],
+            [
+                intro_frame_filepath.as_deref(),
+                outro_frame_filepath.as_deref(),
+            ],
+            [
+                intro_audio_filepath.as_deref(),
+                outro_audio_filepath.as_deref(),
+            ],
+            cache_mode,
+        );
+        println!("Inferred windows (paste into {}):", CONFIG_FILENAME);
+        // windows given explicitly are kept
+        for (window, short, gt, lt) in [
+            (
+                intro_window,
+                "i",
+                &mut args.intro_gt_duration,
+                &mut args.intro_lt_duration,
+            ),
+            (
+                outro_window,
+                "o",
+                &mut args.outro_gt_duration,
+                &mut args.outro_lt_duration,
+            ),
+        ] {
+            let Some((greater_than, less_than)) = window else {
+                continue;
+            };
+            println!("{short}fgt = {greater_than}");
+            println!("{short}flt = {less_than}");
+            if gt.is_empty() {
+                *gt = greater_than.to_string();
+            }
+            if lt.is_empty() {
+                *lt = less_than.to_string();
+            }
+        }
+    }
+    let intro_voting = build_voting(
+        &args,
+        Side::Intro,
+        intro_frame_filepath.as_deref(),
+        intro_audio_filepath.as_deref(),
+    );
+    let outro_voting = build_voting(
+        &args,
+        Side::Outro,
+        outro_frame_filepath.as_deref(),
+        outro_audio_filepath.as_deref(),
+    );
+    // references configured for voting are not detected on their own
+    let intro_frame_filepath = intro_frame_filepath.filter(|_| intro_voting.is_none());
+    let intro_audio_filepath = intro_audio_filepath.filter(|_| intro_voting.is_none());
+    let outro_frame_filepath = outro_frame_filepath.filter(|_| outro_voting.is_none());
+    let outro_audio_filepath = outro_audio_filepath.filter(|_| outro_voting.is_none());
+    let detect_intro = intro_frame_filepath.is_some()
+        || intro_audio_filepath.is_some()
+        || intro_voting.is_some()
+        || args.discover;
+    let detect_outro = outro_frame_filepath.is_some()
+        || outro_audio_filepath.is_some()
+        || outro_voting.is_some()
+        || args.detect_credits;
+
+    let mut discovered: Vec<Option<MatchInterval>> = Vec::new();
+    if args.discover {
+        let discover_pathes: Vec<String> = file_pathes
+            .iter()
+            .map(|(_, file_path)| file_path.to_owned())
+            .collect();
+        discovered = discover_intros(&discover_pathes, &args.discover_minutes, &args.audio_match);
+    }
+
+    let files_count = file_pathes.len();
+    let mut previous_file_path: Option<String> = None;
+    for (file_index, (file_name, file_path)) in file_pathes.into_iter().enumerate() {
+        let previous_file_path = previous_file_path.replace(file_path.clone());
+        let mut cache = FileCache::open(&file_path, cache_mode);
+        let media_info = cache.media_info(|| probe_media(&file_path));
+        let duration = media_info.duration;
+        // audio files (podcasts, audiobooks) have no frames to match, at most a cover art
+        let has_video = media_info.has_video;
+        if !args.make_screenshot.is_empty() && !has_video {
+            println!("{} has no video, screenshot is skipped", file_name);
+        }
+        if !args.make_screenshot.is_empty() && has_video {
             let mut path_buf = PathBuf::from(&file_path);
             path_buf.set_extension("jpg");
             let os_string = path_buf.into_os_string();
             let screenshot_filepath = os_string
                 .to_str()
                 .expect("Unable to cast path buffer to string");
-            make_screenshot(&file_path, &screenshot_filepath, &args.make_screenshot);
+            make_screenshot(&file_path, screenshot_filepath, &args.make_screenshot);
         }
         if args.trim_start.is_empty()
-            && args.intro_frame.is_empty()
+            && !detect_intro
             && args.trim_end.is_empty()
-            && args.outro_frame.is_empty()
+            && !detect_outro
+            && !args.detect_junk
+            && !args.trim_silence
+            && !args.remove_ads
         {
             continue;
         }
-        let duration = calc_duration(&file_path);
-        let mut last_intro_frame_time = -1.0;
-        let mut first_outro_frame_time = -1.0;
-        if !args.intro_frame.is_empty() {
-            if let Some(frame_filepath) = dir_path.join(args.intro_frame.clone()).to_str() {
-                last_intro_frame_time = detect_frame(
-                    &file_path,
-                    frame_filepath,
-                    duration,
-                    &args.intro_blackframe_amount,
-                    &args.intro_blackframe_threshold,
-                    &args.intro_gt_duration,
-                    &args.intro_lt_duration,
-                    false,
-                ) + intro_add;
+        if !has_video
+            && (intro_frame_filepath.is_some()
+                || outro_frame_filepath.is_some()
+                || args.detect_junk
+                || args.detect_credits)
+        {
+            println!("{} has no video, frame detection is skipped", file_name);
+        }
+        let mut references: Vec<FrameReference> = Vec::new();
+        if let Some(frame_filepath) = intro_frame_filepath.as_deref().filter(|_| has_video) {
+            references.push(FrameReference {
+                frame_filepath,
+                blackframe_amount: &args.intro_blackframe_amount,
+                blackframe_threshold: &args.intro_blackframe_threshold,
+                match_threshold: &args.intro_match_threshold,
+                greater_than_duration: &args.intro_gt_duration,
+                less_than_duration: &args.intro_lt_duration,
+            });
+        }
+        if let Some(frame_filepath) = outro_frame_filepath.as_deref().filter(|_| has_video) {
+            references.push(FrameReference {
+                frame_filepath,
+                blackframe_amount: &args.outro_blackframe_amount,
+                blackframe_threshold: &args.outro_blackframe_threshold,
+                match_threshold: &args.outro_match_threshold,
+                greater_than_duration: &args.outro_gt_duration,
+                less_than_duration: &args.outro_lt_duration,
+            });
+        }
+        let mut intro_interval: Option<(&str, MatchInterval)> = None;
+        let mut outro_interval: Option<(&str, MatchInterval)> = None;
+        if let Some(voting) = &intro_voting {
+            let detected = voting.detect(&file_path, &media_info, &mut cache);
+            println!("\nIntro vote {:?}", detected);
+            intro_interval = detected.first().map(|interval| (voting.name(), *interval));
+        }
+        if let Some(voting) = &outro_voting {
+            let detected = voting.detect(&file_path, &media_info, &mut cache);
+            println!("\nOutro vote {:?}", detected);
+            outro_interval = detected.first().map(|interval| (voting.name(), *interval));
+        }
+        let mut timeline_scores: Vec<Vec<(f32, f32)>> = Vec::new();
+        if !references.is_empty() {
+            let sample_rate: f32 = args
+                .sample_rate
+                .parse()
+                .expect("Unable to parse frame sample rate");
+            // intro and outro share a single decode of the file, detectors read the cached scores
+            timeline_scores = cached_frame_scores(
+                &mut cache,
+                &file_path,
+                &references,
+                args.match_metric,
+                sample_rate,
+            );
+        }
+        if let Some(frame_filepath) = intro_frame_filepath.as_deref().filter(|_| has_video) {
+            let detector = build_detector(&args, Side::Intro, "frame", Some(frame_filepath), None);
+            let detected = detector.detect(&file_path, &media_info, &mut cache);
+            println!("\nIntro matches {:?}", detected);
+            intro_interval = detected.last().map(|interval| (detector.name(), *interval));
+        }
+        if let Some(frame_filepath) = outro_frame_filepath.as_deref().filter(|_| has_video) {
+            let detector = build_detector(&args, Side::Outro, "frame", Some(frame_filepath), None);
+            let detected = detector.detect(&file_path, &media_info, &mut cache);
+            println!("\nOutro matches {:?}", detected);
+            outro_interval = detected
+                .first()
+                .map(|interval| (detector.name(), *interval));
+        }
+        // audio is decoded only for the side that frame detection did not resolve
+        let intro_audio = intro_audio_filepath
+            .as_deref()
+            .filter(|_| intro_interval.is_none());
+        let outro_audio = outro_audio_filepath
+            .as_deref()
+            .filter(|_| outro_interval.is_none());
+        if let (Some(intro_clip), Some(outro_clip)) = (intro_audio, outro_audio) {
+            // intro and outro share a single decode of the file, detectors read the cached matches
+            let audio_references = [
+                AudioReference {
+                    clip_filepath: intro_clip,
+                    greater_than_duration: &args.intro_gt_duration,
+                    less_than_duration: &args.intro_lt_duration,
+                },
+                AudioReference {
+                    clip_filepath: outro_clip,
+                    greater_than_duration: &args.outro_gt_duration,
+                    less_than_duration: &args.outro_lt_duration,
+                },
+            ];
+            cached_audio_matches(&mut cache, &file_path, &audio_references, &args.audio_match);
+        }
+        if let Some(clip_filepath) = intro_audio {
+            let detector = build_detector(&args, Side::Intro, "audio", None, Some(clip_filepath));
+            let detected = detector.detect(&file_path, &media_info, &mut cache);
+            println!("\nIntro audio matches {:?}", detected);
+            intro_interval = detected.last().map(|interval| (detector.name(), *interval));
+        }
+        if let Some(clip_filepath) = outro_audio {
+            let detector = build_detector(&args, Side::Outro, "audio", None, Some(clip_filepath));
+            let detected = detector.detect(&file_path, &media_info, &mut cache);
+            println!("\nOutro audio matches {:?}", detected);
+            outro_interval = detected
+                .first()
+                .map(|interval| (detector.name(), *interval));
+        }
+        // cut where leading segments end and trailing ones start, segments closer than gap are chained
+        let mut edge_detectors: Vec<(Box<dyn Detector>, f32)> = Vec::new();
+        if args.trim_silence {
+            let detector = build_detector(&args, Side::Intro, "silence", None, None);
+            edge_detectors.push((detector, SILENCE_GAP));
+        }
+        if args.detect_junk && has_video {
+            edge_detectors.push((Box::new(JunkDetector), JUNK_GAP));
+        }
+        for (detector, gap) in &edge_detectors {
+            if intro_interval.is_some() && outro_interval.is_some() {
+                break;
+            }
+            let detected = detector.detect(&file_path, &media_info, &mut cache);
+            println!("\n{} segments {:?}", detector.name(), detected);
+            if intro_interval.is_none() {
+                intro_interval = lead_in_end(&detected, *gap).map(|end| {
+                    let interval = MatchInterval {
+                        start: 0.0,
+                        end,
+                        peak: 0.0,
+                        frames: 0,
+                    };
+                    (detector.name(), interval)
+                });
+            }
+            if outro_interval.is_none() {
+                outro_interval = trailing_start(&detected, duration, *gap).map(|start| {
+                    let interval = MatchInterval {
+                        start,
+                        end: duration,
+                        peak: 0.0,
+                        frames: 0,
+                    };
+                    (detector.name(), interval)
+                });
+            }
+        }
+        if args.detect_credits && has_video && outro_interval.is_none() {
+            let detector = build_detector(&args, Side::Outro, "credits", None, None);
+            let detected = detector.detect(&file_path, &media_info, &mut cache);
+            println!("\nCredits {:?}", detected);
+            outro_interval = credits_start(&detected).map(|interval| (detector.name(), interval));
+        }
+        if intro_interval.is_none() {
+            if let Some(Some(interval)) = discovered.get(file_index) {
+                println!("\nIntro discovered {:?}", interval);
+                intro_interval = Some(("discover", *interval));
+            }
+        }
+        let mut intro_cut = match intro_interval {
+            Some((detector, interval)) => {
+                let offset = match BOUNDARY_DETECTORS.contains(&detector) {
+                    true => 0.0,
+                    false => intro_offset,
+                };
+                let time = interval.end + offset;
+                Cut::detected(detector, time, time, offset, interval)
+            }
+            None => {
+                if detect_intro && !allow_fallback(args.on_no_match, "intro", &file_name) {
+                    continue;
+                }
+                Cut::manual(&args.trim_start, "--ts")
+            }
+        };
+        let mut outro_cut = match outro_interval {
+            Some((detector, interval)) => {
+                let offset = match BOUNDARY_DETECTORS.contains(&detector) {
+                    true => 0.0,
+                    false => outro_offset,
+                };
+                let time = interval.start + offset;
+                Cut::detected(detector, duration - time, time, offset, interval)
+            }
+            None => {
+                if detect_outro && !allow_fallback(args.on_no_match, "outro", &file_name) {
+                    continue;
+                }
+                Cut::manual(&args.trim_end, "--te")
+            }
+        };
+        let silence_radius = parse_time(&args.snap_silence);
+        let silence_intervals = match silence_radius.is_some() || args.remove_ads {
+            true => cache.detection(
+                "silence",
+                &[&args.silence_noise, &args.silence_duration],
+                || {
+                    detect_silence(
+                        &file_path,
+                        duration,
+                        &args.silence_noise,
+                        &args.silence_duration,
+                    )
+                },
+            ),
+            false => Vec::new(),
+        };
+        let black_radius = parse_time(&args.snap_black).filter(|_| has_video);
+        let scene_radius = parse_time(&args.snap_scene).filter(|_| has_video);
+        let black_scenes = match has_video
+            && (black_radius.is_some() || scene_radius.is_some() || args.remove_ads)
+        {
+            true => {
+                let scene_threshold = scene_radius.map(|_| args.scene_threshold.as_str());
+                cache.detection(
+                    "black",
+                    &[
+                        &args.black_duration,
+                        &args.black_threshold,
+                        scene_threshold.unwrap_or(""),
+                    ],
+                    || {
+                        detect_black_scenes(
+                            &file_path,
+                            &args.black_duration,
+                            &args.black_threshold,
+                            scene_threshold,
+                        )
+                    },
+                )
+            }
+            false => BlackScenes {
+                black: Vec::new(),
+                scenes: Vec::new(),
+            },
+        };
+        let subtitles_radius = parse_time(&args.snap_subtitles);
+        let subtitle_boundaries = match subtitles_radius.is_some() && media_info.has_subtitles {
+            true => {
+                let subtitle_gap =
+                    parse_time(&args.subtitle_gap).expect("Unable to parse subtitle gap");
+                let gaps = cache.detection("subtitles", &[&subtitle_gap.to_string()], || {
+                    detect_subtitle_gaps(&file_path, duration, subtitle_gap)
+                });
+                println!("\nSubtitle gaps {:?}", gaps);
+                // gaps outside --ifgt/--iflt and --ofgt/--oflt are no boundary
+                [
+                    gap_boundaries(
+                        &gaps,
+                        Side::Intro,
+                        args.intro_gt_duration.parse().unwrap_or(-1.0),
+                        args.intro_lt_duration.parse().unwrap_or(-1.0),
+                    ),
+                    gap_boundaries(
+                        &gaps,
+                        Side::Outro,
+                        args.outro_gt_duration.parse().unwrap_or(-1.0),
+                        args.outro_lt_duration.parse().unwrap_or(-1.0),
+                    ),
+                ]
+            }
+            false => [Vec::new(), Vec::new()],
+        };
+        let mut snappers: Vec<Snapper> = Vec::new();
+        if let Some(radius) = silence_radius {
+            println!("\nSilences {:?}", silence_intervals);
+            snappers.push(Snapper {
+                name: "silence",
+                intervals: &silence_intervals,
+                radius,
+            });
+        }
+        if let Some(radius) = black_radius {
+            println!("\nBlack segments {:?}", black_scenes.black);
+            snappers.push(Snapper {
+                name: "black",
+                intervals: &black_scenes.black,
+                radius,
+            });
+        }
+        if let Some(radius) = scene_radius {
+            println!("\nScene changes {:?}", black_scenes.scenes);
+            snappers.push(Snapper {
+                name: "scene",
+                intervals: &black_scenes.scenes,
+                radius,
+            });
+        }
+        let mut intro_snappers = snappers.clone();
+        let mut outro_snappers = snappers;
+        if let Some(radius) = subtitles_radius {
+            // intro ends with the first line after the gap, outro starts after the last line
+            intro_snappers.push(Snapper {
+                name: "subtitles",
+                intervals: &subtitle_boundaries[0],
+                radius,
+            });
+            outro_snappers.push(Snapper {
+                name: "subtitles",
+                intervals: &subtitle_boundaries[1],
+                radius,
+            });
+        }
+        intro_cut.snap(duration, false, &intro_snappers);
+        outro_cut.snap(duration, true, &outro_snappers);
+        let mut remove_segments: Vec<(f32, f32)> = Vec::new();
+        let intro_time = intro_cut.detected_time(duration, false);
+        if let (true, Some(previous_file_path), Some(intro_time)) = (
+            args.remove_recap && has_video,
+            &previous_file_path,
+            intro_time,
+        ) {
+            let sample_rate: f32 = args
+                .sample_rate
+                .parse()
+                .expect("Unable to parse frame sample rate");
+            let sample_rate_str = sample_rate.to_string();
+            // whole files are hashed so that every file is decoded once as current and previous
+            let previous_frames: Vec<HashedFrame> = FileCache::open(previous_file_path, cache_mode)
+                .detection("hashes", &[&sample_rate_str], || {
+                    hash_frames(previous_file_path, sample_rate)
+                });
+            let frames: Vec<HashedFrame> = cache.detection("hashes", &[&sample_rate_str], || {
+                hash_frames(&file_path, sample_rate)
+            });
+            let classified = classify_frames(&frames, &previous_frames, intro_time);
+            if let Some((trim_start, removed)) = recap_cuts(&classified, intro_time, sample_rate) {
                 println!(
-                    "\nDetected last intro frame {} (add {})",
-                    last_intro_frame_time, intro_add
+                    "\nRecap of {}: cold open from {}, removed {:?}",
+                    file_name, trim_start, removed
                 );
+                let cold_open = MatchInterval {
+                    start: trim_start,
+                    end: intro_time,
+                    peak: 0.0,
+                    frames: classified
+                        .iter()
+                        .filter(|(_, kind)| *kind == FrameKind::Unique)
+                        .count() as u32,
+                };
+                // intro end stays the reported boundary, the file is trimmed from the cold open
+                intro_cut = Cut::detected("recap", intro_time, intro_time, 0.0, cold_open);
+                intro_cut.retrim(duration, false, trim_start);
+                remove_segments.extend(removed);
             }
         }
+        // the cold open before a discovered intro is kept, only the intro is removed
+        if let (
+            CutSource::Detected {
+                detector: "discover",
+                interval,
+                ..
+            },
+            Some(intro_time),
+        ) = (&intro_cut.source, intro_cut.detected_time(duration, false))
+        {
+            if interval.start > DISCOVER_LEAD_IN && args.skip_encoding {
+                println!(
+                    "\nCold open of {} is trimmed with the intro, keeping it requires encoding",
+                    file_name
+                );
+            } else if interval.start > DISCOVER_LEAD_IN {
+                println!(
+                    "\nCold open of {}: removing intro {} - {}",
+                    file_name, interval.start, intro_time
+                );
+                remove_segments.push((interval.start, intro_time));
+                intro_cut.retrim(duration, false, 0.0);
+            }
+        }
+        println!(
+            "{} => trim start {}, trim end {}",
+            file_name, intro_cut, outro_cut
+        );
+        if !args.debug_timeline.is_empty() && !timeline_scores.is_empty() {
+            let mut series: Vec<TimelineSeries> = Vec::new();
+            for (index, reference) in references.iter().enumerate() {
+                let (name, boundary) = match index == 0 && intro_frame_filepath.is_some() {
+                    true => ("Intro", intro_cut.detected_time(duration, false)),
+                    false => ("Outro", outro_cut.detected_time(duration, true)),
+                };
+                let mut reference_series = TimelineSeries {
+                    name: format!(
+                        "{} {} ({:?})",
+                        name, reference.frame_filepath, args.match_metric
+                    ),
+                    scores: timeline_scores[index].clone(),
+                    greater_than: reference.greater_than_duration.parse().unwrap_or(-1.0),
+                    less_than: reference.less_than_duration.parse().unwrap_or(-1.0),
+                    threshold: min_score(args.match_metric, reference),
+                    boundary,
+                    thumbnails: Vec::new(),
+                };
+                reference_series.thumbnails = peaks(&reference_series, duration)
+                    .into_iter()
+                    .filter_map(|(time, _)| thumbnail(&file_path, time).map(|data| (time, data)))
+                    .collect();
+                series.push(reference_series);
+            }
+            write_timeline(
+                &timeline_filepath(&args.debug_timeline, &file_name, files_count),
+                &file_name,
+                duration,
+                &series,
+            );
+        }
+        if evaluate_labels.is_some() {
+            evaluated.push((
+                file_name.to_owned(),
+                intro_cut.detected_time(duration, false),
+                outro_cut.detected_time(duration, true),
+            ));
+            continue;
+        }
+        let mut trim_end: &str = &outro_cut.trim;
+        if args.after_outro == AfterOutro::Keep {
+            if let CutSource::Detected {
+                detector: "frame", ..
+            } = outro_cut.source
+            {
+                println!(
+                    "\nWarning: outro of {} is matched by a still frame, its end is the last matching frame, not the end of outro (use --outro-audio or --detect-credits)",
+                    file_name
+                );
+            }
+            if let Some((outro_start, outro_end)) = outro_cut.outro_segment(duration) {
+                println!(
+                    "\nOutro of {}: removing {} - {}, keeping the rest",
+                    file_name, outro_start, outro_end
+                );
+                remove_segments.push((outro_start, outro_end));
+                trim_end = "";
+            } else if outro_cut.detected_time(duration, true).is_some() {
+                // e.g. voting reports the agreed boundary only, start and end are the same
+                println!(
+                    "\nWarning: --after-outro keep can not be applied to {}, outro match has no end, everything after outro start is removed",
+                    file_name
+                );
+            }
+        }
+        if args.remove_ads {
+            let markers = break_markers(&black_scenes.black, &silence_intervals);
+            let ad_blocks = group_ad_blocks(
+                &markers,
+                parse_time(&args.ad_min).expect("Unable to parse ad block min length"),
+                parse_time(&args.ad_max).expect("Unable to parse ad block max length"),
+            );
+            println!(
+                "\nAd blocks of {} ({} break markers):",
+                file_name,
+                markers.len()
+            );
+            for block in &ad_blocks {
+                println!(
+                    "  {} - {} ({} s, {} markers)",
+                    block.start, block.end, block.peak, block.frames
+                );
+            }
+            let mut confirmed = true;
+            if args.confirm_ads && !ad_blocks.is_empty() && !args.test_images {
+                print!("Remove these ad blocks? [y/N] ");
+                stdout().flush().expect("Can not flush stdout");
+                let mut answer = String::new();
+                stdin()
+                    .read_line(&mut answer)
+                    .expect("Unable to read stdin into line");
+                confirmed = answer.trim().eq_ignore_ascii_case("y");
+            }
+            if confirmed {
+                remove_segments.extend(ad_blocks.iter().map(|block| (block.start, block.end)));
+            }
+        }
+        let last_intro_frame_time = match intro_cut.source {
+            CutSource::Detected { time, .. } => time,
+            _ => -1.0,
+        };
+        let first_outro_frame_time = match outro_cut.source {
+            CutSource::Detected { time, .. } => time,
+            _ => -1.0,
+        };
         let last_intro_frame_time_str = last_intro_frame_time.to_string();
-        if args.test_images && last_intro_frame_time >= 0.0 {
+        if args.test_images && has_video && last_intro_frame_time >= 0.0 {
             let screenshot_filename = format!("{}_intro.jpg", file_name);
             let mut path_buf = PathBuf::from(&file_path);
             path_buf.set_file_name(screenshot_filename);
@@ -240,29 +1614,10 @@ fn main() {
             let screenshot_filepath = os_string
                 .to_str()
                 .expect("Unable to cast path buffer to string");
-            make_screenshot(&file_path, &screenshot_filepath, &last_intro_frame_time_str);
+            make_screenshot(&file_path, screenshot_filepath, &last_intro_frame_time_str);
         }
-        if !args.outro_frame.is_empty() {
-            if let Some(frame_filepath) = dir_path.join(args.outro_frame.clone()).to_str() {
-                first_outro_frame_time = detect_frame(
-                    &file_path,
-                    frame_filepath,
-                    duration,
-                    &args.outro_blackframe_amount,
-                    &args.outro_blackframe_threshold,
-                    &args.outro_gt_duration,
-                    &args.outro_lt_duration,
-                    true,
-                ) + outro_add;
-                println!(
-                    "\nDetected first outro frame {} (add {})",
-                    first_outro_frame_time, outro_add
-                );
-            }
-        }
-        let outro_frame_from_end_str = (duration - first_outro_frame_time).to_string();
         let first_outro_frame_time_str = first_outro_frame_time.to_string();
-        if args.test_images && first_outro_frame_time >= 0.0 {
+        if args.test_images && has_video && first_outro_frame_time >= 0.0 {
             let screenshot_filename = format!("{}_outro.jpg", file_name);
             let mut path_buf = PathBuf::from(&file_path);
             path_buf.set_file_name(screenshot_filename);
@@ -270,11 +1625,7 @@ fn main() {
             let screenshot_filepath = os_string
                 .to_str()
                 .expect("Unable to cast path buffer to string");
-            make_screenshot(
-                &file_path,
-                &screenshot_filepath,
-                &first_outro_frame_time_str,
-            );
+            make_screenshot(&file_path, screenshot_filepath, &first_outro_frame_time_str);
         }
         if args.test_images {
             continue;
@@ -282,18 +1633,32 @@ fn main() {
         trim_start_end(
             &file_path,
             duration,
-            match last_intro_frame_time >= 0.0 {
-                true => &last_intro_frame_time_str,
-                false => &args.trim_start,
+            &intro_cut.trim,
+            trim_end,
+            &TrimOptions {
+                skip_encoding: args.skip_encoding,
+                map_video: &args.map_video,
+                map_audio: &args.map_audio,
+                remove_segments: &remove_segments,
+                audio_only: !has_video,
+                fade_in,
+                fade_out,
+                join_dip,
             },
-            match first_outro_frame_time >= 0.0 {
-                true => &outro_frame_from_end_str,
-                false => &args.trim_end,
-            },
-            args.skip_encoding,
-            &args.map_video,
-            &args.map_audio,
         )
     }
+    if let Some(labels) = &evaluate_labels {
+        // skipped files count as not detected
+        let detected: Vec<(Option<f32>, Option<f32>)> = labels
+            .iter()
+            .map(|label| {
+                evaluated
+                    .iter()
+                    .find(|(file_name, _, _)| file_name == &label.file)
+                    .map_or((None, None), |&(_, intro, outro)| (intro, outro))
+            })
+            .collect();
+        print_report(labels, &detected);
+    }
     println!("DONE");
 }
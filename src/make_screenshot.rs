@@ -21,8 +21,9 @@ pub fn make_screenshot(input_filepath: &str, output_filepath: &str, frame_time:
         .expect("Unable to spawn child process")
         .iter()
         .expect("Unable to obtain child process iterator")
-        .for_each(|e| match e {
-            FfmpegEvent::Error(err) => println!("Error making screenshot\n{}", err),
-            _ => {}
+        .for_each(|e| {
+            if let FfmpegEvent::Error(err) = e {
+                println!("Error making screenshot\n{}", err)
+            }
         });
 }
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+/// Frames closer than this (in seconds) belong to the same interval
+pub const MAX_FRAME_GAP: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MatchInterval {
+    pub start: f32,
+    pub end: f32,
+    /// best score inside the interval, e.g. pblack for blackframe
+    pub peak: f32,
+    /// number of matching frames inside the interval
+    pub frames: u32,
+}
+
+#[cfg(test)]
+impl MatchInterval {
+    /// Interval scored by its length, e.g. a silence
+    pub fn new(start: f32, end: f32) -> MatchInterval {
+        MatchInterval {
+            start,
+            end,
+            peak: end - start,
+            frames: 0,
+        }
+    }
+}
+
+/// Groups `(time, score)` hits sorted by time into contiguous intervals.
+pub fn collect_intervals(hits: &[(f32, f32)], max_gap: f32) -> Vec<MatchInterval> {
+    let mut intervals: Vec<MatchInterval> = Vec::new();
+
+    for &(time, score) in hits {
+        match intervals.last_mut() {
+            Some(interval) if time - interval.end <= max_gap => {
+                interval.end = time;
+                interval.peak = interval.peak.max(score);
+                interval.frames += 1;
+            }
+            _ => intervals.push(MatchInterval {
+                start: time,
+                end: time,
+                peak: score,
+                frames: 1,
+            }),
+        }
+    }
+
+    intervals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_intervals, MatchInterval, MAX_FRAME_GAP};
+
+    #[test]
+    fn collect_intervals_for_nothing() {
+        let intervals = collect_intervals(&[], MAX_FRAME_GAP);
+
+        assert!(intervals.is_empty());
+    }
+
+    #[test]
+    fn collect_intervals_single_frame() {
+        let intervals = collect_intervals(&[(12.0, 97.0)], MAX_FRAME_GAP);
+
+        assert_eq!(
+            intervals,
+            vec![MatchInterval {
+                start: 12.0,
+                end: 12.0,
+                peak: 97.0,
+                frames: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn collect_intervals_splits_on_gap() {
+        let intervals = collect_intervals(
+            &[
+                (10.0, 95.0),
+                (11.0, 99.0),
+                (12.0, 96.0),
+                (40.0, 98.0),
+                (41.0, 97.0),
+            ],
+            MAX_FRAME_GAP,
+        );
+
+        assert_eq!(
+            intervals,
+            vec![
+                MatchInterval {
+                    start: 10.0,
+                    end: 12.0,
+                    peak: 99.0,
+                    frames: 3
+                },
+                MatchInterval {
+                    start: 40.0,
+                    end: 41.0,
+                    peak: 98.0,
+                    frames: 2
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/// Side of the square grayscale picture hashes are computed from
+pub const HASH_SIZE: u32 = 32;
+/// Hashes are 64 bit long
+pub const HASH_BITS: u32 = 64;
+
+/// Averages `size` x `size` grayscale picture down to `width` x `height`.
+fn downscale(gray: &[u8], size: usize, width: usize, height: usize) -> Vec<f32> {
+    let mut scaled = vec![0.0; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let (x0, x1) = (
+                x * size / width,
+                ((x + 1) * size / width).max(x * size / width + 1),
+            );
+            let (y0, y1) = (
+                y * size / height,
+                ((y + 1) * size / height).max(y * size / height + 1),
+            );
+            let mut sum: f32 = 0.0;
+            for yy in y0..y1 {
+                for xx in x0..x1 {
+                    sum += gray[yy * size + xx] as f32;
+                }
+            }
+            scaled[y * width + x] = sum / ((x1 - x0) * (y1 - y0)) as f32;
+        }
+    }
+
+    scaled
+}
+
+/// Difference hash: 9x8 picture, one bit per horizontal neighbour comparison.
+pub fn dhash(gray: &[u8]) -> u64 {
+    let scaled = downscale(gray, HASH_SIZE as usize, 9, 8);
+    let mut hash: u64 = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            if scaled[y * 9 + x] < scaled[y * 9 + x + 1] {
+                hash |= 1 << (y * 8 + x);
+            }
+        }
+    }
+
+    hash
+}
+
+/// Perceptual hash: lowest 8x8 DCT coefficients compared with their median.
+pub fn phash(gray: &[u8]) -> u64 {
+    let size = HASH_SIZE as usize;
+    let cosines: Vec<f32> = (0..8)
+        .flat_map(|u| {
+            (0..size).map(move |x| ((2 * x + 1) as f32 * u as f32 * PI / (2 * size) as f32).cos())
+        })
+        .collect();
+
+    let mut coefficients = [0.0_f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            let mut sum: f32 = 0.0;
+            for y in 0..size {
+                for x in 0..size {
+                    sum +=
+                        gray[y * size + x] as f32 * cosines[u * size + x] * cosines[v * size + y];
+                }
+            }
+            coefficients[v * 8 + u] = sum;
+        }
+    }
+
+    // the DC coefficient only tells average brightness
+    let mut sorted: Vec<f32> = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    let mut hash: u64 = 0;
+    for (i, coefficient) in coefficients.iter().enumerate().skip(1) {
+        if *coefficient > median {
+            hash |= 1 << i;
+        }
+    }
+
+    hash
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrameHash {
+    pub dhash: u64,
+    pub phash: u64,
+}
+
+impl FrameHash {
+    pub fn new(gray: &[u8]) -> FrameHash {
+        FrameHash {
+            dhash: dhash(gray),
+            phash: phash(gray),
+        }
+    }
+
+    /// Average Hamming distance of both hashes (0..64)
+    pub fn distance(&self, other: &FrameHash) -> f32 {
+        ((self.dhash ^ other.dhash).count_ones() + (self.phash ^ other.phash).count_ones()) as f32
+            / 2.0
+    }
+
+    /// Similarity percentage (100 means identical hashes)
+    pub fn similarity(&self, other: &FrameHash) -> f32 {
+        100.0 * (1.0 - self.distance(other) / HASH_BITS as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dhash, FrameHash, HASH_SIZE};
+
+    fn gradient(flip: bool) -> Vec<u8> {
+        let size = HASH_SIZE as usize;
+        (0..size * size)
+            .map(|i| {
+                let x = (i % size) as u8;
+                match flip {
+                    true => 255 - x * 8,
+                    false => x * 8,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dhash_of_gradient() {
+        assert_eq!(dhash(&gradient(false)), u64::MAX);
+        assert_eq!(dhash(&gradient(true)), 0);
+    }
+
+    /// Blocks of pseudo-random brightness
+    fn blocks(seed: u32) -> Vec<u8> {
+        let size = HASH_SIZE as usize;
+        (0..size * size)
+            .map(|i| {
+                let block = ((i / size) / 4 * 8 + (i % size) / 4) as u32;
+                (block.wrapping_add(seed).wrapping_mul(2654435761) >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_hash_similarity() {
+        let picture = blocks(1);
+        let mut noisy = picture.clone();
+        noisy[5] = noisy[5].saturating_add(3);
+
+        let hash = FrameHash::new(&picture);
+        assert_eq!(hash.similarity(&hash), 100.0);
+        assert!(hash.similarity(&FrameHash::new(&noisy)) > 90.0);
+        assert!(hash.similarity(&FrameHash::new(&blocks(2))) < 75.0);
+    }
+}
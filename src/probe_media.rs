@@ -0,0 +1,119 @@
+use std::{
+    io::{BufReader, Read},
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub duration: f32,
+    pub has_video: bool,
+    pub has_audio: bool,
+    pub has_subtitles: bool,
+}
+
+/// Parses `ffprobe -show_entries format=duration:stream=codec_type:stream_disposition=attached_pic -of csv=p=0` output.
+/// Cover art (attached picture) of audio files is not a video.
+pub fn parse_media_info(output: &str) -> Option<MediaInfo> {
+    let mut media_info = MediaInfo {
+        duration: 0.0,
+        has_video: false,
+        has_audio: false,
+        has_subtitles: false,
+    };
+
+    for line in output.lines().map(|line| line.trim()) {
+        match line {
+            "video" | "video,0" => media_info.has_video = true,
+            "video,1" => {}
+            _ if line.starts_with("audio") => media_info.has_audio = true,
+            _ if line.starts_with("subtitle") => media_info.has_subtitles = true,
+            _ => {
+                if let Ok(duration) = line.parse::<f32>() {
+                    media_info.duration = duration;
+                }
+            }
+        }
+    }
+
+    match media_info.duration > 0.0 {
+        true => Some(media_info),
+        false => None,
+    }
+}
+
+pub fn probe_media(filepath: &str) -> MediaInfo {
+    let mut child = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration:stream=codec_type:stream_disposition=attached_pic",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(filepath)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stdout = child.stdout.take().expect("No child stdout");
+
+    let mut reader = BufReader::new(stdout);
+
+    let mut result = String::new();
+
+    reader.read_to_string(&mut result).unwrap();
+
+    let status = child.wait().unwrap();
+
+    if status.success() {
+        if let Some(media_info) = parse_media_info(&result) {
+            return media_info;
+        }
+    }
+    panic!("Unable to probe {filepath}! Result is {result}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_media_info, MediaInfo};
+
+    #[test]
+    fn parse_media_info_for_video() {
+        assert_eq!(
+            parse_media_info("video\naudio\naudio\nsubtitle\n1422.080000\n"),
+            Some(MediaInfo {
+                duration: 1422.08,
+                has_video: true,
+                has_audio: true,
+                has_subtitles: true,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_media_info_with_cover_art() {
+        assert_eq!(
+            parse_media_info("audio,0\nvideo,1\n3600.500000\n"),
+            Some(MediaInfo {
+                duration: 3600.5,
+                has_video: false,
+                has_audio: true,
+                has_subtitles: false,
+            })
+        );
+        assert!(
+            parse_media_info("video,0\naudio,0\n60\n")
+                .unwrap()
+                .has_video
+        );
+    }
+
+    #[test]
+    fn parse_media_info_for_nothing() {
+        assert_eq!(parse_media_info(""), None);
+        assert_eq!(parse_media_info("audio\nN/A\n"), None);
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    env, fs,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{helpers::parse_time, make_screenshot::make_screenshot};
+
+/// Library inside the home directory when `--refs` is not given
+const DEFAULT_REFS_DIRNAME: &str = ".ffmpegtrim/refs";
+const IMAGE_EXTENSION: &str = "jpg";
+const META_EXTENSION: &str = "json";
+
+/// Where a reference frame was grabbed from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefMeta {
+    pub source: String,
+    /// seconds from start of `source`
+    pub time: f32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Splits `myshow/intro` into show and reference name.
+pub fn parse_ref_name(reference: &str) -> Option<(&str, &str)> {
+    let (show, name) = reference.split_once('/')?;
+    match show.is_empty() || name.is_empty() || name.contains('/') {
+        true => None,
+        false => Some((show, name)),
+    }
+}
+
+/// Parses `ffprobe -select_streams v:0 -show_entries stream=width,height -of csv=p=0` output.
+pub fn parse_resolution(output: &str) -> Option<(u32, u32)> {
+    let (width, height) = output.lines().next()?.trim().split_once(',')?;
+
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+fn probe_resolution(filepath: &Path) -> (u32, u32) {
+    let mut child = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(filepath)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stdout = child.stdout.take().expect("No child stdout");
+
+    let mut reader = BufReader::new(stdout);
+
+    let mut result = String::new();
+
+    reader.read_to_string(&mut result).unwrap();
+
+    let status = child.wait().unwrap();
+
+    match status.success() {
+        true => parse_resolution(&result).unwrap_or_else(|| {
+            panic!("Unable to parse {filepath:?} resolution! Result is {result}")
+        }),
+        false => panic!("Unable to probe {filepath:?}! Result is {result}"),
+    }
+}
+
+/// Named reference frames, `<dir>/<show>/<name>.jpg` with metadata next to every image.
+pub struct RefLibrary {
+    pub dir: PathBuf,
+}
+
+impl RefLibrary {
+    /// `refs_dir`, or `~/.ffmpegtrim/refs` when empty
+    pub fn open(refs_dir: &str) -> RefLibrary {
+        let dir = match refs_dir.is_empty() {
+            true => env::var("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default()
+                .join(DEFAULT_REFS_DIRNAME),
+            false => PathBuf::from(refs_dir),
+        };
+
+        RefLibrary { dir }
+    }
+
+    fn filepath(&self, show: &str, name: &str, extension: &str) -> PathBuf {
+        self.dir.join(show).join(format!("{name}.{extension}"))
+    }
+
+    /// Image of `myshow/intro` when the library has it
+    pub fn resolve(&self, reference: &str) -> Option<PathBuf> {
+        let (show, name) = parse_ref_name(reference)?;
+        let image_filepath = self.filepath(show, name, IMAGE_EXTENSION);
+
+        image_filepath.is_file().then_some(image_filepath)
+    }
+
+    /// Grabs the frame at `time` of `video_filepath`, an existing reference is replaced.
+    pub fn add(&self, show: &str, name: &str, video_filepath: &str, time: &str) -> RefMeta {
+        let seconds = parse_time(time).expect("Unable to parse reference time");
+        let image_filepath = self.filepath(show, name, IMAGE_EXTENSION);
+        fs::create_dir_all(self.dir.join(show)).expect("Unable to create refs directory");
+        fs::remove_file(&image_filepath).ok();
+        make_screenshot(
+            video_filepath,
+            image_filepath
+                .to_str()
+                .expect("Unable to cast path buffer to string"),
+            time,
+        );
+        if !image_filepath.is_file() {
+            panic!("Unable to grab frame at {time} from {video_filepath}");
+        }
+        let (width, height) = probe_resolution(&image_filepath);
+        let source = fs::canonicalize(video_filepath)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| video_filepath.to_owned());
+        let meta = RefMeta {
+            source,
+            time: seconds,
+            width,
+            height,
+        };
+        let content = serde_json::to_string_pretty(&meta).expect("Unable to serialize reference");
+        fs::write(self.filepath(show, name, META_EXTENSION), content)
+            .expect("Unable to write reference metadata");
+
+        meta
+    }
+
+    /// References sorted by name (`show/name`), of one show when `show` is not empty.
+    /// Metadata is missing for images copied into the library by hand.
+    pub fn list(&self, show: &str) -> Vec<(String, Option<RefMeta>)> {
+        let mut refs: Vec<(String, Option<RefMeta>)> = Vec::new();
+        let Ok(show_entries) = fs::read_dir(&self.dir) else {
+            return refs;
+        };
+        for show_entry in show_entries.flatten() {
+            let show_name = show_entry.file_name().to_string_lossy().into_owned();
+            if !show_entry.path().is_dir() || (!show.is_empty() && show_name != show) {
+                continue;
+            }
+            for entry in fs::read_dir(show_entry.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                let path = entry.path();
+                if path
+                    .extension()
+                    .is_none_or(|extension| extension != IMAGE_EXTENSION)
+                {
+                    continue;
+                }
+                let Some(name) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+                    continue;
+                };
+                let meta = fs::read_to_string(path.with_extension(META_EXTENSION))
+                    .ok()
+                    .and_then(|content| serde_json::from_str(&content).ok());
+                refs.push((format!("{show_name}/{name}"), meta));
+            }
+        }
+        refs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        refs
+    }
+
+    /// Removes image and metadata, returns false when there was no such reference.
+    pub fn delete(&self, show: &str, name: &str) -> bool {
+        let deleted = fs::remove_file(self.filepath(show, name, IMAGE_EXTENSION)).is_ok();
+        fs::remove_file(self.filepath(show, name, META_EXTENSION)).ok();
+        // drop the show directory with its last reference
+        fs::remove_dir(self.dir.join(show)).ok();
+
+        deleted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::{parse_ref_name, parse_resolution, RefLibrary, RefMeta};
+
+    #[test]
+    fn parse_ref_name_show_and_name() {
+        assert_eq!(parse_ref_name("myshow/intro"), Some(("myshow", "intro")));
+        assert_eq!(parse_ref_name("intro.jpg"), None);
+        assert_eq!(parse_ref_name("/intro"), None);
+        assert_eq!(parse_ref_name("refs/myshow/intro"), None);
+    }
+
+    #[test]
+    fn parse_resolution_of_image() {
+        assert_eq!(parse_resolution("1920,1080\n"), Some((1920, 1080)));
+        assert_eq!(parse_resolution(""), None);
+    }
+
+    #[test]
+    fn library_list_resolve_delete() {
+        let library = RefLibrary {
+            dir: env::temp_dir().join(format!("ffmpegtrim_refs_test_{}", process::id())),
+        };
+        fs::remove_dir_all(&library.dir).ok();
+        fs::create_dir_all(library.dir.join("myshow")).unwrap();
+        fs::write(library.dir.join("myshow/outro.jpg"), "jpg").unwrap();
+        fs::write(library.dir.join("myshow/intro.jpg"), "jpg").unwrap();
+        let meta = RefMeta {
+            source: "/video/S01E01.mkv".to_owned(),
+            time: 83.0,
+            width: 1920,
+            height: 1080,
+        };
+        fs::write(
+            library.dir.join("myshow/intro.json"),
+            serde_json::to_string(&meta).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            library.list(""),
+            vec![
+                ("myshow/intro".to_owned(), Some(meta)),
+                ("myshow/outro".to_owned(), None)
+            ]
+        );
+        assert!(library.list("other").is_empty());
+        assert_eq!(
+            library.resolve("myshow/intro"),
+            Some(library.dir.join("myshow/intro.jpg"))
+        );
+        assert_eq!(library.resolve("myshow/ending"), None);
+
+        assert!(library.delete("myshow", "intro"));
+        assert!(!library.delete("myshow", "intro"));
+        assert_eq!(library.list("myshow").len(), 1);
+        fs::remove_dir_all(&library.dir).ok();
+    }
+}
@@ -0,0 +1,72 @@
+use crate::match_interval::MatchInterval;
+
+/// Candidates a cut may be moved to, e.g. silence intervals
+#[derive(Clone)]
+pub struct Snapper<'a> {
+    pub name: &'static str,
+    pub intervals: &'a [MatchInterval],
+    /// maximum distance in seconds a cut may be moved
+    pub radius: f32,
+}
+
+/// Nearest point inside any interval within `radius` of `time`.
+pub fn snap_time(time: f32, intervals: &[MatchInterval], radius: f32) -> Option<f32> {
+    intervals
+        .iter()
+        .map(|interval| time.clamp(interval.start, interval.end.max(interval.start)))
+        .filter(|snapped| (snapped - time).abs() <= radius)
+        .min_by(|a, b| (a - time).abs().total_cmp(&(b - time).abs()))
+}
+
+/// Nearest snap over all snappers, with the name of the snapper that won.
+pub fn snap_nearest(time: f32, snappers: &[Snapper]) -> Option<(f32, &'static str)> {
+    snappers
+        .iter()
+        .filter_map(|snapper| {
+            snap_time(time, snapper.intervals, snapper.radius)
+                .map(|snapped| (snapped, snapper.name))
+        })
+        .min_by(|a, b| (a.0 - time).abs().total_cmp(&(b.0 - time).abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{snap_nearest, snap_time, Snapper};
+    use crate::match_interval::MatchInterval;
+
+    #[test]
+    fn snap_time_to_nearest_interval() {
+        let intervals = [
+            MatchInterval::new(10.0, 11.0),
+            MatchInterval::new(20.0, 20.5),
+        ];
+
+        assert_eq!(snap_time(12.0, &intervals, 2.0), Some(11.0));
+        assert_eq!(snap_time(19.0, &intervals, 2.0), Some(20.0));
+        assert_eq!(snap_time(10.5, &intervals, 2.0), Some(10.5));
+        assert_eq!(snap_time(15.0, &intervals, 2.0), None);
+        assert_eq!(snap_time(15.0, &[], 2.0), None);
+    }
+
+    #[test]
+    fn snap_nearest_over_snappers() {
+        let silence = [MatchInterval::new(10.0, 11.0)];
+        let black = [MatchInterval::new(12.5, 12.5)];
+        let snappers = [
+            Snapper {
+                name: "silence",
+                intervals: &silence,
+                radius: 2.0,
+            },
+            Snapper {
+                name: "black",
+                intervals: &black,
+                radius: 1.0,
+            },
+        ];
+
+        assert_eq!(snap_nearest(12.0, &snappers), Some((12.5, "black")));
+        assert_eq!(snap_nearest(11.2, &snappers), Some((11.0, "silence")));
+        assert_eq!(snap_nearest(20.0, &snappers), None);
+    }
+}
@@ -0,0 +1,301 @@
+use std::{env, fs, path::Path, process};
+
+use crate::make_screenshot::make_screenshot;
+
+const WIDTH: f32 = 1000.0;
+const PANEL_HEIGHT: f32 = 220.0;
+/// Space above the plot of every panel for its title
+const TITLE_HEIGHT: f32 = 30.0;
+const THUMBNAIL_WIDTH: f32 = 160.0;
+const THUMBNAIL_HEIGHT: f32 = 90.0;
+/// Thumbnails per series at most
+const MAX_THUMBNAILS: usize = 3;
+
+/// Frame scores of one reference with everything detection decided on.
+pub struct TimelineSeries {
+    pub name: String,
+    pub scores: Vec<(f32, f32)>,
+    /// `--ifgt`/`--iflt` window, -1 means no limit
+    pub greater_than: f32,
+    pub less_than: f32,
+    /// lowest matching score
+    pub threshold: f32,
+    /// chosen cut time
+    pub boundary: Option<f32>,
+    /// time and base64 encoded JPEG of the best scored frames
+    pub thumbnails: Vec<(f32, String)>,
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => {
+                    encoded.push(BASE64_ALPHABET[(triple >> (18 - 6 * i) & 63) as usize] as char)
+                }
+                false => encoded.push('='),
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Time and score of the best frames inside the window, sorted by time.
+/// Peaks are a thumbnail width apart so that their thumbnails do not overlap.
+pub fn peaks(series: &TimelineSeries, duration: f32) -> Vec<(f32, f32)> {
+    let min_distance = duration * THUMBNAIL_WIDTH / WIDTH;
+    let mut scores: Vec<(f32, f32)> = series
+        .scores
+        .iter()
+        .filter(|&&(time, _)| {
+            (series.greater_than == -1.0 || time > series.greater_than)
+                && (series.less_than == -1.0 || series.less_than > time)
+        })
+        .copied()
+        .collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut peaks: Vec<(f32, f32)> = Vec::new();
+    for (time, score) in scores {
+        if peaks.len() == MAX_THUMBNAILS {
+            break;
+        }
+        if peaks
+            .iter()
+            .all(|peak| (peak.0 - time).abs() >= min_distance)
+        {
+            peaks.push((time, score));
+        }
+    }
+    peaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    peaks
+}
+
+/// Screenshot at `time` as base64 encoded JPEG.
+pub fn thumbnail(input_filepath: &str, time: f32) -> Option<String> {
+    let screenshot_filepath =
+        env::temp_dir().join(format!("ffmpegtrim_thumbnail_{}_{time}.jpg", process::id()));
+    let screenshot_filepath = screenshot_filepath
+        .to_str()
+        .expect("Unable to cast path buffer to string");
+    make_screenshot(input_filepath, screenshot_filepath, &time.to_string());
+    let data = fs::read(screenshot_filepath).ok()?;
+    fs::remove_file(screenshot_filepath).ok();
+
+    Some(base64(&data))
+}
+
+/// Plots every series in its own panel: scores, window, threshold line, boundary and thumbnails.
+pub fn timeline_svg(title: &str, duration: f32, series: &[TimelineSeries]) -> String {
+    let height = TITLE_HEIGHT + PANEL_HEIGHT * series.len() as f32;
+    let x = |time: f32| time.clamp(0.0, duration) / duration * WIDTH;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" font-family="sans-serif" font-size="12">"#
+    );
+    svg.push_str(&format!(
+        r#"<text x="4" y="18" font-size="14">{} ({duration}s)</text>"#,
+        escape(title)
+    ));
+
+    for (index, series) in series.iter().enumerate() {
+        let top = TITLE_HEIGHT + PANEL_HEIGHT * index as f32;
+        let plot_top = top + TITLE_HEIGHT;
+        let plot_height = PANEL_HEIGHT - TITLE_HEIGHT - 10.0;
+        // infinite scores (identical frames for psnr) are drawn at the top
+        let finite = || {
+            series
+                .scores
+                .iter()
+                .map(|score| score.1)
+                .chain([series.threshold])
+                .filter(|score| score.is_finite())
+        };
+        let min = finite().fold(f32::MAX, f32::min);
+        let max = finite().fold(f32::MIN, f32::max);
+        let range = (max - min).max(f32::EPSILON);
+        let y = |score: f32| plot_top + plot_height * (1.0 - (score.min(max) - min) / range);
+
+        svg.push_str(&format!(
+            r##"<text x="4" y="{}">{} (threshold {}, range {min:.2}..{max:.2})</text>"##,
+            top + 22.0,
+            escape(&series.name),
+            series.threshold
+        ));
+        let window_start = match series.greater_than == -1.0 {
+            true => 0.0,
+            false => x(series.greater_than),
+        };
+        let window_end = match series.less_than == -1.0 {
+            true => WIDTH,
+            false => x(series.less_than),
+        };
+        svg.push_str(&format!(
+            r##"<rect x="{window_start}" y="{plot_top}" width="{}" height="{plot_height}" fill="#dde8f8"/>"##,
+            (window_end - window_start).max(0.0)
+        ));
+        let points: Vec<String> = series
+            .scores
+            .iter()
+            .map(|&(time, score)| format!("{:.1},{:.1}", x(time), y(score)))
+            .collect();
+        svg.push_str(&format!(
+            r##"<polyline points="{}" fill="none" stroke="#333" stroke-width="1"/>"##,
+            points.join(" ")
+        ));
+        svg.push_str(&format!(
+            r##"<line x1="0" y1="{0}" x2="{WIDTH}" y2="{0}" stroke="#d33" stroke-dasharray="4"/>"##,
+            y(series.threshold)
+        ));
+        if let Some(boundary) = series.boundary {
+            svg.push_str(&format!(
+                r##"<line x1="{0}" y1="{plot_top}" x2="{0}" y2="{1}" stroke="#2a2" stroke-width="2"/>"##,
+                x(boundary),
+                plot_top + plot_height
+            ));
+        }
+        for (time, data) in &series.thumbnails {
+            let left = (x(*time) - THUMBNAIL_WIDTH / 2.0).clamp(0.0, WIDTH - THUMBNAIL_WIDTH);
+            svg.push_str(&format!(
+                r##"<line x1="{0}" y1="{plot_top}" x2="{0}" y2="{1}" stroke="#e90" stroke-dasharray="2"/>"##,
+                x(*time),
+                plot_top + plot_height
+            ));
+            svg.push_str(&format!(
+                r#"<image x="{left}" y="{plot_top}" width="{THUMBNAIL_WIDTH}" height="{THUMBNAIL_HEIGHT}" href="data:image/jpeg;base64,{data}"/>"#
+            ));
+        }
+    }
+    svg.push_str("</svg>");
+
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Writes the timeline as SVG, or as HTML page when `output_filepath` ends with `.html`.
+pub fn write_timeline(
+    output_filepath: &str,
+    title: &str,
+    duration: f32,
+    series: &[TimelineSeries],
+) {
+    let svg = timeline_svg(title, duration, series);
+    let content = match Path::new(output_filepath)
+        .extension()
+        .is_some_and(|extension| extension == "html")
+    {
+        true => format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title></head><body>\n{}\n</body></html>\n",
+            escape(title),
+            svg
+        ),
+        false => svg,
+    };
+    fs::write(output_filepath, content).expect("Unable to write debug timeline");
+    println!("Debug timeline written to {output_filepath}");
+}
+
+/// Timeline path of one input: `out.svg` for a single file, `out_<file name>.svg` otherwise.
+pub fn timeline_filepath(output_filepath: &str, file_name: &str, files_count: usize) -> String {
+    if files_count <= 1 {
+        return output_filepath.to_owned();
+    }
+    let path = Path::new(output_filepath);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("timeline");
+    let file_name = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("{stem}_{file_name}.{extension}"),
+        None => format!("{stem}_{file_name}"),
+    };
+
+    path.with_file_name(file_name)
+        .to_str()
+        .expect("Unable to cast path buffer to string")
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, peaks, timeline_filepath, timeline_svg, TimelineSeries};
+
+    fn series() -> TimelineSeries {
+        TimelineSeries {
+            name: "intro hash".to_owned(),
+            scores: vec![(0.0, 50.0), (10.0, 95.0), (20.0, 99.0), (30.0, 60.0)],
+            greater_than: 5.0,
+            less_than: 15.0,
+            threshold: 75.0,
+            boundary: Some(10.0),
+            thumbnails: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn base64_with_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn peaks_inside_window() {
+        assert_eq!(peaks(&series(), 40.0), vec![(10.0, 95.0)]);
+
+        let mut series = series();
+        series.scores = vec![
+            (0.0, 90.0),
+            (4.0, 99.0),
+            (6.0, 98.0),
+            (20.0, 50.0),
+            (39.0, 80.0),
+        ];
+        series.greater_than = -1.0;
+        series.less_than = -1.0;
+        // thumbnails are 6.4 seconds wide, 6.0 is too close to 4.0
+        assert_eq!(
+            peaks(&series, 40.0),
+            vec![(4.0, 99.0), (20.0, 50.0), (39.0, 80.0)]
+        );
+    }
+
+    #[test]
+    fn timeline_svg_elements() {
+        let svg = timeline_svg("e01.mkv", 40.0, &[series()]);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        // window from 5 to 15 seconds of 40
+        assert!(svg.contains(r#"<rect x="125" y="60" width="250""#));
+        assert!(svg.contains(r#"points="0.0,240.0 250.0,"#));
+        assert!(svg.contains(r#"<line x1="250" "#));
+    }
+
+    #[test]
+    fn timeline_filepath_per_file() {
+        assert_eq!(timeline_filepath("out.svg", "e01.mkv", 1), "out.svg");
+        assert_eq!(
+            timeline_filepath("dbg/out.html", "e01.mkv", 2),
+            "dbg/out_e01.mkv.html"
+        );
+    }
+}
@@ -14,17 +14,145 @@ pub struct CommandResult {
     pub input_filename: String,
     pub output_filename: String,
     pub duration: f32,
+    #[allow(dead_code)]
     pub seconds_from_start: f32,
 }
 
+#[derive(Default)]
+pub struct TrimOptions<'a> {
+    pub skip_encoding: bool,
+    pub map_video: &'a str,
+    pub map_audio: &'a str,
+    /// `(start, end)` segments of the input (in seconds) removed from the output
+    pub remove_segments: &'a [(f32, f32)],
+    /// input without video, cover art is copied as is
+    pub audio_only: bool,
+    /// seconds of fade from black/silence at the start and to black/silence at the end of output
+    pub fade_in: f32,
+    pub fade_out: f32,
+    /// seconds of dip to black/silence on both sides of every join of removed segments
+    pub join_dip: f32,
+}
+
+/// Encoder of the output container for audio-only files, ffmpeg default when unknown.
+pub fn audio_codec(extension: &str) -> Option<&'static str> {
+    match extension.to_lowercase().as_str() {
+        "mp3" => Some("libmp3lame"),
+        "m4a" | "m4b" | "aac" => Some("aac"),
+        "flac" => Some("flac"),
+        "ogg" | "oga" => Some("libvorbis"),
+        "opus" => Some("libopus"),
+        "wav" => Some("pcm_s16le"),
+        _ => None,
+    }
+}
+
+pub struct SegmentFilters {
+    pub video: String,
+    pub audio: String,
+    /// total seconds removed
+    pub removed: f32,
+    /// output times (in seconds) where kept parts meet
+    pub joins: Vec<f32>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FadeFilters {
+    pub video: Vec<String>,
+    pub audio: Vec<String>,
+}
+
+/// Builds select/aselect filters dropping `segments` between `from` and `to` of the input.
+/// Output timestamps start at `from` because of the input seek.
+pub fn remove_segments_filters(
+    segments: &[(f32, f32)],
+    from: f32,
+    to: f32,
+) -> Option<SegmentFilters> {
+    let mut removed: f32 = 0.0;
+    let mut joins: Vec<f32> = Vec::new();
+    let mut clipped: Vec<(f32, f32)> = segments
+        .iter()
+        .map(|&(start, end)| (start.max(from), end.min(to)))
+        .filter(|(start, end)| end > start)
+        .collect();
+    clipped.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let betweens: Vec<String> = clipped
+        .iter()
+        .map(|&(start, end)| {
+            let join = start - from - removed;
+            if join > 0.0 && end < to && joins.last() != Some(&join) {
+                joins.push(join);
+            }
+            removed += end - start;
+            format!("between(t,{},{})", start - from, end - from)
+        })
+        .collect();
+    if betweens.is_empty() {
+        return None;
+    }
+    let expression = format!("not({})", betweens.join("+"));
+
+    Some(SegmentFilters {
+        video: format!("select='{}',setpts=N/FRAME_RATE/TB", expression),
+        audio: format!("aselect='{}',asetpts=N/SR/TB", expression),
+        removed,
+        joins,
+    })
+}
+
+/// Builds fade/afade filters on the output timeline of `duration` seconds,
+/// joins get a short fade out and in (a volume dip for audio).
+pub fn fade_filters(
+    duration: f32,
+    fade_in: f32,
+    fade_out: f32,
+    joins: &[f32],
+    join_dip: f32,
+) -> FadeFilters {
+    let mut filters = FadeFilters::default();
+    if join_dip > 0.0 && !joins.is_empty() {
+        for &join in joins {
+            let start = (join - join_dip).max(0.0);
+            filters.video.push(format!(
+                "fade=t=out:st={start}:d={join_dip}:enable='between(t,{start},{join})'"
+            ));
+            filters.video.push(format!(
+                "fade=t=in:st={join}:d={join_dip}:enable='between(t,{join},{})'",
+                join + join_dip
+            ));
+        }
+        let dips: Vec<String> = joins
+            .iter()
+            .map(|join| format!("min(1,abs(t-{join})/{join_dip})"))
+            .collect();
+        filters
+            .audio
+            .push(format!("volume='{}':eval=frame", dips.join("*")));
+    }
+    if fade_in > 0.0 {
+        filters.video.push(format!("fade=t=in:st=0:d={fade_in}"));
+        filters.audio.push(format!("afade=t=in:st=0:d={fade_in}"));
+    }
+    if fade_out > 0.0 {
+        let start = (duration - fade_out).max(0.0);
+        filters
+            .video
+            .push(format!("fade=t=out:st={start}:d={fade_out}"));
+        filters
+            .audio
+            .push(format!("afade=t=out:st={start}:d={fade_out}"));
+    }
+
+    filters
+}
+
 pub fn trim_start_end_command(
     input_filepath: &str,
     duration: f32,
     trim_start: &str,
     trim_end: &str,
-    skip_encoding: bool,
-    map_video: &str,
-    map_audio: &str,
+    options: &TrimOptions,
 ) -> CommandResult {
     if trim_start.contains("dur") && trim_end.contains("dur") {
         panic!("Both trim-start and trim-end options use duration value, only one allowed");
@@ -84,131 +212,100 @@ pub fn trim_start_end_command(
         command.args(["-to", &new_duration.to_string()]);
     }
     command.args(["-i", input_filepath, "-progress", "pipe:2"]);
-    if !map_video.is_empty() {
-        let video_index: u32 = map_video
+    if !options.map_video.is_empty() {
+        let video_index: u32 = options
+            .map_video
             .parse()
             .expect("Unable to parse video stream index");
         command.args(["-map", format!("0:v:{}", video_index).as_str()]);
     }
-    if !map_audio.is_empty() {
-        let audio_index: u32 = map_audio
+    if !options.map_audio.is_empty() {
+        let audio_index: u32 = options
+            .map_audio
             .parse()
             .expect("Unable to parse audio stream index");
         command.args(["-map", format!("0:a:{}", audio_index).as_str()]);
+    } else if options.audio_only {
+        command.args(["-map", "0:a"]);
     }
-    if skip_encoding {
+    if options.audio_only {
+        // cover art (attached picture) and ID3/Vorbis tags
+        command.args(["-map", "0:v?", "-map_metadata", "0"]);
+    }
+    let segment_filters = remove_segments_filters(
+        options.remove_segments,
+        seconds_from_start.max(0.0),
+        if new_duration > 0.0 {
+            new_duration
+        } else {
+            duration
+        },
+    );
+    let output_duration = (if new_duration > 0.0 {
+        new_duration
+    } else {
+        duration
+    }) - (if seconds_from_start > 0.0 {
+        seconds_from_start
+    } else {
+        0.0
+    }) - segment_filters
+        .as_ref()
+        .map_or(0.0, |segment_filters| segment_filters.removed);
+    if options.skip_encoding {
+        if segment_filters.is_some() {
+            panic!("Removing segments requires encoding, skip encoding is not supported");
+        }
+        if options.fade_in > 0.0 || options.fade_out > 0.0 {
+            panic!("Fades require encoding, skip encoding is not supported");
+        }
         command.args(["-c", "copy"]);
     } else {
-        // command.args(["-flags", "+ildct+ilme"]); // keep interlace frame
-        command.args(["-vf", "yadif"]); // remove interlacing
+        let fades = fade_filters(
+            output_duration,
+            options.fade_in,
+            options.fade_out,
+            segment_filters
+                .as_ref()
+                .map_or(&[], |segment_filters| &segment_filters.joins),
+            options.join_dip,
+        );
+        let mut video_filters: Vec<String> = Vec::new();
+        let mut audio_filters: Vec<String> = Vec::new();
+        if let Some(segment_filters) = &segment_filters {
+            video_filters.push(segment_filters.video.to_owned());
+            audio_filters.push(segment_filters.audio.to_owned());
+        }
+        video_filters.extend(fades.video);
+        audio_filters.extend(fades.audio);
+        if options.audio_only {
+            command.args(["-c:v", "copy"]);
+            if let Some(codec) = audio_codec(extension) {
+                command.args(["-c:a", codec]);
+            }
+        } else {
+            // command.args(["-flags", "+ildct+ilme"]); // keep interlace frame
+            video_filters.insert(0, "yadif".to_owned()); // remove interlacing
+            command.args(["-vf", &video_filters.join(",")]);
+        }
+        if !audio_filters.is_empty() {
+            command.args(["-af", &audio_filters.join(",")]);
+        }
     }
-    command.arg(output_filepath.to_owned());
+    command.arg(&output_filepath);
     let command_str = command
         .get_args()
         .map(|arg| arg.to_str().unwrap())
         .collect::<Vec<&str>>()
         .join(" ");
 
-    return CommandResult {
+    CommandResult {
         command,
         command_str,
         input_filename: input_filename.to_owned(),
         output_filename: output_filename.to_owned(),
-        duration: (if new_duration > 0.0 {
-            new_duration
-        } else {
-            duration
-        }) - (if seconds_from_start > 0.0 {
-            seconds_from_start
-        } else {
-            0.0
-        }),
+        duration: output_duration,
         seconds_from_start,
-    };
-}
-
-#[cfg(test)]
-mod tests {
-    use super::trim_start_end_command;
-
-    #[test]
-    fn calc_command_result_basic() {
-        let command_result = trim_start_end_command("/f.f", 10.0, "", "", false, "", "");
-
-        assert_eq!(command_result.input_filename, "f.f");
-        assert_eq!(command_result.output_filename, "f_tr.f");
-        assert_eq!(command_result.duration, 10.0);
-        assert_eq!(command_result.seconds_from_start, 0.0);
-        assert_eq!(
-            command_result.command_str,
-            "-i /f.f -progress pipe:2 -vf yadif /f_tr.f"
-        );
-    }
-
-    #[test]
-    fn calc_command_result_trim_start() {
-        let command_result = trim_start_end_command("/a.mp4", 10.0, "4.5", "", true, "", "");
-
-        assert_eq!(command_result.input_filename, "a.mp4");
-        assert_eq!(command_result.output_filename, "a_tr.mp4");
-        assert_eq!(command_result.duration, 5.5);
-        assert_eq!(command_result.seconds_from_start, 4.5);
-        assert_eq!(
-            command_result.command_str,
-            "-ss 4.5 -i /a.mp4 -progress pipe:2 -c copy /a_tr.mp4"
-        );
-    }
-
-    #[test]
-    fn calc_command_result_trim_end() {
-        let command_result = trim_start_end_command("/b.mp4", 10.0, "", "5.46", false, "0", "1");
-
-        assert_eq!(command_result.input_filename, "b.mp4");
-        assert_eq!(command_result.output_filename, "b_tr.mp4");
-        assert_eq!(command_result.duration, 4.54);
-        assert_eq!(command_result.seconds_from_start, 0.0);
-        assert_eq!(
-            command_result.command_str,
-            "-to 4.54 -i /b.mp4 -progress pipe:2 -map 0:v:0 -map 0:a:1 -vf yadif /b_tr.mp4"
-        );
-    }
-
-    #[test]
-    fn calc_command_result_trim_both() {
-        let command_result =
-            trim_start_end_command("/some/c.mp4", 10.0, "1.52", "3.33", true, "", "0");
-
-        assert_eq!(command_result.input_filename, "c.mp4");
-        assert_eq!(command_result.output_filename, "c_tr.mp4");
-        assert_eq!(command_result.duration, 5.15);
-        assert_eq!(command_result.seconds_from_start, 1.52);
-        assert_eq!(
-            command_result.command_str,
-            "-ss 1.52 -to 6.67 -i /some/c.mp4 -progress pipe:2 -map 0:a:0 -c copy /some/c_tr.mp4"
-        );
-    }
-
-    #[test]
-    #[should_panic(
-        expected = "Both trim-start and trim-end options use duration value, only one allowed"
-    )]
-    fn calc_command_result_panic_both_dur() {
-        trim_start_end_command("/some/c.mp4", 0.0, "1dur1", "1dur1", false, "", "");
-    }
-
-    #[test]
-    fn calc_command_result_trim_start_dur() {
-        let command_result =
-            trim_start_end_command("/s/d.mp4", 10.0, "1.52dur4.5", "", false, "", "0");
-
-        assert_eq!(command_result.input_filename, "d.mp4");
-        assert_eq!(command_result.output_filename, "d_tr.mp4");
-        assert_eq!(command_result.duration, 4.5);
-        assert_eq!(command_result.seconds_from_start, 1.52);
-        assert_eq!(
-            command_result.command_str,
-            "-ss 1.52 -to 6.02 -i /s/d.mp4 -progress pipe:2 -map 0:a:0 -vf yadif /s/d_tr.mp4"
-        );
     }
 }
 
@@ -217,20 +314,11 @@ pub fn trim_start_end(
     duration: f32,
     trim_start: &str,
     trim_end: &str,
-    skip_encoding: bool,
-    map_video: &str,
-    map_audio: &str,
+    options: &TrimOptions,
 ) {
     let mut soft_exit = false;
-    let mut command_result = trim_start_end_command(
-        input_filepath,
-        duration,
-        trim_start,
-        trim_end,
-        skip_encoding,
-        map_video,
-        map_audio,
-    );
+    let mut command_result =
+        trim_start_end_command(input_filepath, duration, trim_start, trim_end, options);
 
     println!(
         "Input => {}\nDuration => {}\nffmpeg {}\nOutput => {}",
@@ -261,14 +349,13 @@ pub fn trim_start_end(
         .expect("Unable to read from child stderr");
 
     while len > 0 {
-        for i in 0..len {
-            let char_code = buff_stderr[i];
+        for &char_code in buff_stderr.iter().take(len) {
             buff_str.push(char_code as char);
             if char_code == 32 {
                 // space
                 if buff_str.contains("Overwrite? [y/N]") {
                     self_stdout
-                        .write(buff_str.as_bytes())
+                        .write_all(buff_str.as_bytes())
                         .expect("Unable to write to stdout");
                     self_stdout.flush().expect("Can not flush stdout"); // force stdout to print without end of line
                     buff_str.clear();
@@ -280,9 +367,8 @@ pub fn trim_start_end(
                         .expect("Unable to read stdin into line");
                     let child_stdin = child.stdin.as_mut().expect("No child stdin");
                     child_stdin
-                        .write(answer.as_bytes())
+                        .write_all(answer.as_bytes())
                         .expect("Unable to write to child stdin");
-                    drop(child_stdin);
                 }
             } else if char_code == 10 {
                 // line end
@@ -324,7 +410,7 @@ pub fn trim_start_end(
     }
     let mut self_stdout = stdout();
     self_stdout
-        .write(buff_str.as_bytes())
+        .write_all(buff_str.as_bytes())
         .expect("Unable to write to stdout");
 
     let status = child.wait().unwrap();
@@ -334,3 +420,280 @@ pub fn trim_start_end(
 
     panic!("Unable to complete decode!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        audio_codec, fade_filters, remove_segments_filters, trim_start_end_command, TrimOptions,
+    };
+
+    #[test]
+    fn calc_command_result_basic() {
+        let command_result = trim_start_end_command("/f.f", 10.0, "", "", &TrimOptions::default());
+
+        assert_eq!(command_result.input_filename, "f.f");
+        assert_eq!(command_result.output_filename, "f_tr.f");
+        assert_eq!(command_result.duration, 10.0);
+        assert_eq!(command_result.seconds_from_start, 0.0);
+        assert_eq!(
+            command_result.command_str,
+            "-i /f.f -progress pipe:2 -vf yadif /f_tr.f"
+        );
+    }
+
+    #[test]
+    fn calc_command_result_trim_start() {
+        let command_result = trim_start_end_command(
+            "/a.mp4",
+            10.0,
+            "4.5",
+            "",
+            &TrimOptions {
+                skip_encoding: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(command_result.input_filename, "a.mp4");
+        assert_eq!(command_result.output_filename, "a_tr.mp4");
+        assert_eq!(command_result.duration, 5.5);
+        assert_eq!(command_result.seconds_from_start, 4.5);
+        assert_eq!(
+            command_result.command_str,
+            "-ss 4.5 -i /a.mp4 -progress pipe:2 -c copy /a_tr.mp4"
+        );
+    }
+
+    #[test]
+    fn calc_command_result_trim_end() {
+        let command_result = trim_start_end_command(
+            "/b.mp4",
+            10.0,
+            "",
+            "5.46",
+            &TrimOptions {
+                map_video: "0",
+                map_audio: "1",
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(command_result.input_filename, "b.mp4");
+        assert_eq!(command_result.output_filename, "b_tr.mp4");
+        assert_eq!(command_result.duration, 4.54);
+        assert_eq!(command_result.seconds_from_start, 0.0);
+        assert_eq!(
+            command_result.command_str,
+            "-to 4.54 -i /b.mp4 -progress pipe:2 -map 0:v:0 -map 0:a:1 -vf yadif /b_tr.mp4"
+        );
+    }
+
+    #[test]
+    fn calc_command_result_trim_both() {
+        let command_result = trim_start_end_command(
+            "/some/c.mp4",
+            10.0,
+            "1.52",
+            "3.33",
+            &TrimOptions {
+                skip_encoding: true,
+                map_audio: "0",
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(command_result.input_filename, "c.mp4");
+        assert_eq!(command_result.output_filename, "c_tr.mp4");
+        assert_eq!(command_result.duration, 5.15);
+        assert_eq!(command_result.seconds_from_start, 1.52);
+        assert_eq!(
+            command_result.command_str,
+            "-ss 1.52 -to 6.67 -i /some/c.mp4 -progress pipe:2 -map 0:a:0 -c copy /some/c_tr.mp4"
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Both trim-start and trim-end options use duration value, only one allowed"
+    )]
+    fn calc_command_result_panic_both_dur() {
+        trim_start_end_command(
+            "/some/c.mp4",
+            0.0,
+            "1dur1",
+            "1dur1",
+            &TrimOptions::default(),
+        );
+    }
+
+    #[test]
+    fn calc_command_result_trim_start_dur() {
+        let command_result = trim_start_end_command(
+            "/s/d.mp4",
+            10.0,
+            "1.52dur4.5",
+            "",
+            &TrimOptions {
+                map_audio: "0",
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(command_result.input_filename, "d.mp4");
+        assert_eq!(command_result.output_filename, "d_tr.mp4");
+        assert_eq!(command_result.duration, 4.5);
+        assert_eq!(command_result.seconds_from_start, 1.52);
+        assert_eq!(
+            command_result.command_str,
+            "-ss 1.52 -to 6.02 -i /s/d.mp4 -progress pipe:2 -map 0:a:0 -vf yadif /s/d_tr.mp4"
+        );
+    }
+
+    #[test]
+    fn calc_command_result_remove_segments() {
+        let command_result = trim_start_end_command(
+            "/e.mp4",
+            100.0,
+            "10",
+            "20",
+            &TrimOptions {
+                remove_segments: &[(5.0, 15.0), (30.0, 40.5), (90.0, 95.0)],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(command_result.duration, 54.5);
+        assert_eq!(command_result.seconds_from_start, 10.0);
+        assert_eq!(
+            command_result.command_str,
+            "-ss 10 -to 80 -i /e.mp4 -progress pipe:2 -vf yadif,select='not(between(t,0,5)+between(t,20,30.5))',setpts=N/FRAME_RATE/TB -af aselect='not(between(t,0,5)+between(t,20,30.5))',asetpts=N/SR/TB /e_tr.mp4"
+        );
+    }
+
+    #[test]
+    fn calc_command_result_audio_only() {
+        let command_result = trim_start_end_command(
+            "/p.mp3",
+            100.0,
+            "10",
+            "",
+            &TrimOptions {
+                remove_segments: &[(30.0, 40.0)],
+                audio_only: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(command_result.duration, 80.0);
+        assert_eq!(
+            command_result.command_str,
+            "-ss 10 -i /p.mp3 -progress pipe:2 -map 0:a -map 0:v? -map_metadata 0 -c:v copy -c:a libmp3lame -af aselect='not(between(t,20,30))',asetpts=N/SR/TB /p_tr.mp3"
+        );
+
+        let command_result = trim_start_end_command(
+            "/b.opus",
+            100.0,
+            "",
+            "5",
+            &TrimOptions {
+                skip_encoding: true,
+                audio_only: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            command_result.command_str,
+            "-to 95 -i /b.opus -progress pipe:2 -map 0:a -map 0:v? -map_metadata 0 -c copy /b_tr.opus"
+        );
+    }
+
+    #[test]
+    fn calc_command_result_fades() {
+        let command_result = trim_start_end_command(
+            "/f.mkv",
+            100.0,
+            "10",
+            "20",
+            &TrimOptions {
+                remove_segments: &[(30.0, 40.0)],
+                fade_in: 0.5,
+                fade_out: 1.0,
+                join_dip: 0.2,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(command_result.duration, 60.0);
+        assert_eq!(
+            command_result.command_str,
+            "-ss 10 -to 80 -i /f.mkv -progress pipe:2 -vf yadif,select='not(between(t,20,30))',setpts=N/FRAME_RATE/TB,fade=t=out:st=19.8:d=0.2:enable='between(t,19.8,20)',fade=t=in:st=20:d=0.2:enable='between(t,20,20.2)',fade=t=in:st=0:d=0.5,fade=t=out:st=59:d=1 -af aselect='not(between(t,20,30))',asetpts=N/SR/TB,volume='min(1,abs(t-20)/0.2)':eval=frame,afade=t=in:st=0:d=0.5,afade=t=out:st=59:d=1 /f_tr.mkv"
+        );
+    }
+
+    #[test]
+    fn remove_segments_joins_on_output_timeline() {
+        // unsorted, the first one starts before the trim start
+        let segment_filters =
+            remove_segments_filters(&[(50.0, 60.0), (5.0, 15.0), (30.0, 40.0)], 10.0, 100.0)
+                .unwrap();
+
+        assert_eq!(segment_filters.removed, 25.0);
+        assert_eq!(segment_filters.joins, vec![15.0, 25.0]);
+    }
+
+    #[test]
+    fn fade_filters_without_joins() {
+        assert_eq!(
+            fade_filters(10.0, 0.0, 0.0, &[5.0], 0.0),
+            Default::default()
+        );
+        let fades = fade_filters(10.0, 0.0, 2.0, &[], 0.2);
+        assert_eq!(fades.video, vec!["fade=t=out:st=8:d=2"]);
+        assert_eq!(fades.audio, vec!["afade=t=out:st=8:d=2"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Fades require encoding")]
+    fn calc_command_result_panic_fade_copy() {
+        trim_start_end_command(
+            "/e.mp4",
+            100.0,
+            "",
+            "",
+            &TrimOptions {
+                skip_encoding: true,
+                fade_in: 1.0,
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn audio_codec_by_extension() {
+        assert_eq!(audio_codec("MP3"), Some("libmp3lame"));
+        assert_eq!(audio_codec("m4b"), Some("aac"));
+        assert_eq!(audio_codec("mka"), None);
+    }
+
+    #[test]
+    fn remove_segments_filters_outside_trim() {
+        assert!(remove_segments_filters(&[], 0.0, 10.0).is_none());
+        assert!(remove_segments_filters(&[(20.0, 30.0)], 0.0, 10.0).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Removing segments requires encoding")]
+    fn calc_command_result_panic_remove_segments_copy() {
+        trim_start_end_command(
+            "/e.mp4",
+            100.0,
+            "",
+            "",
+            &TrimOptions {
+                skip_encoding: true,
+                remove_segments: &[(5.0, 15.0)],
+                ..Default::default()
+            },
+        );
+    }
+}
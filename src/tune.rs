@@ -0,0 +1,434 @@
+use std::{fs, path::Path};
+
+use clap::ValueEnum;
+
+use crate::{
+    config::CONFIG_FILENAME,
+    detect_frame::{frame_scores, score_intervals, FrameReference, MatchMetric},
+    detector::Side,
+    helpers::parse_time,
+};
+
+/// Blackframe pixel thresholds tried, every one needs its own decode
+const BLACKFRAME_THRESHOLDS: [&str; 5] = ["5", "10", "15", "20", "32"];
+/// Sample rates tried (Hz), files are decoded once at the highest one
+const SAMPLE_RATES: [f32; 3] = [1.0, 2.0, 4.0];
+
+/// Frame scores of one file, one list per side
+type FileScores = Vec<Vec<(f32, f32)>>;
+
+/// Match thresholds tried, blackframe amounts for blackframe metric
+fn threshold_grid(metric: MatchMetric) -> &'static [f32] {
+    match metric {
+        MatchMetric::Hash => &[4.0, 8.0, 12.0, 16.0, 20.0, 24.0],
+        MatchMetric::Ssim => &[0.5, 0.6, 0.7, 0.8, 0.85, 0.9, 0.95],
+        MatchMetric::Psnr => &[15.0, 20.0, 25.0, 30.0, 35.0],
+        MatchMetric::Blackframe => &[80.0, 85.0, 90.0, 95.0, 98.0, 99.0],
+    }
+}
+
+/// True boundaries of one file, times are seconds from start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub file: String,
+    pub intro_end: Option<f32>,
+    pub outro_start: Option<f32>,
+}
+
+impl Label {
+    pub fn boundary(&self, side: Side) -> Option<f32> {
+        match side {
+            Side::Intro => self.intro_end,
+            Side::Outro => self.outro_start,
+        }
+    }
+}
+
+/// Parses `file,intro_end,outro_start` lines, e.g. `e01.mkv,1:32.5,`.
+/// Empty time means the file has no such boundary, a header line is skipped.
+pub fn parse_labels(content: &str) -> Vec<Label> {
+    let mut labels: Vec<Label> = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let columns: Vec<&str> = line.split(',').map(|column| column.trim()).collect();
+        let time = |column: usize| -> Result<Option<f32>, ()> {
+            match columns.get(column).copied().unwrap_or("") {
+                "" => Ok(None),
+                value => parse_time(value).map(Some).ok_or(()),
+            }
+        };
+        match (time(1), time(2)) {
+            (Ok(intro_end), Ok(outro_start)) => labels.push(Label {
+                file: columns[0].to_owned(),
+                intro_end,
+                outro_start,
+            }),
+            _ if index == 0 => {}
+            _ => panic!("Unable to parse label line {line}"),
+        }
+    }
+
+    labels
+}
+
+/// Keeps the first score of every `1 / sample_rate` period.
+pub fn subsample(scores: &[(f32, f32)], sample_rate: f32) -> Vec<(f32, f32)> {
+    let mut previous_period: Option<i64> = None;
+
+    scores
+        .iter()
+        .filter(|&&(time, _)| {
+            // small epsilon keeps frame times like 0.4999 in their period
+            let period = (time * sample_rate + 0.001).floor() as i64;
+            let first = previous_period != Some(period);
+            previous_period = Some(period);
+            first
+        })
+        .copied()
+        .collect()
+}
+
+pub fn median(values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+
+    match sorted.len() % 2 {
+        0 => Some((sorted[middle - 1] + sorted[middle]) / 2.0),
+        _ => Some(sorted[middle]),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+    /// seconds added to every detected boundary
+    pub offset: f32,
+    pub mean_error: f32,
+    /// labeled boundaries that were not detected
+    pub misses: usize,
+    /// boundaries detected in files labeled without one
+    pub false_positives: usize,
+    /// absolute error per file, `None` when not labeled or missed
+    pub errors: Vec<Option<f32>>,
+}
+
+impl Fit {
+    /// Fewer misses and false positives first, then lower error
+    pub fn is_better(&self, other: &Fit) -> bool {
+        (self.misses + self.false_positives, self.mean_error)
+            < (other.misses + other.false_positives, other.mean_error)
+    }
+}
+
+/// Compares detected boundaries with labels using the offset with the lowest mean absolute error
+/// (median of differences, rounded to 0.1 s).
+pub fn fit_offset(boundaries: &[Option<f32>], labels: &[Option<f32>]) -> Fit {
+    let differences: Vec<f32> = boundaries
+        .iter()
+        .zip(labels)
+        .filter_map(|pair| match pair {
+            (Some(boundary), Some(label)) => Some(label - boundary),
+            _ => None,
+        })
+        .collect();
+    let offset = median(&differences).map_or(0.0, |median| (median * 10.0).round() / 10.0);
+
+    let errors: Vec<Option<f32>> = boundaries
+        .iter()
+        .zip(labels)
+        .map(|pair| match pair {
+            (Some(boundary), Some(label)) => Some((boundary + offset - label).abs()),
+            _ => None,
+        })
+        .collect();
+    let matched: Vec<f32> = errors.iter().flatten().copied().collect();
+    let misses = labels.iter().flatten().count() - matched.len();
+    let false_positives = boundaries
+        .iter()
+        .zip(labels)
+        .filter(|(boundary, label)| boundary.is_some() && label.is_none())
+        .count();
+    let mean_error = match matched.is_empty() {
+        true => 0.0,
+        false => matched.iter().sum::<f32>() / matched.len() as f32,
+    };
+
+    Fit {
+        offset,
+        mean_error,
+        misses,
+        false_positives,
+        errors,
+    }
+}
+
+/// Reference frame tuned for one side of the files.
+pub struct TuneSide<'a> {
+    pub side: Side,
+    pub frame_filepath: &'a str,
+    pub greater_than_duration: &'a str,
+    pub less_than_duration: &'a str,
+}
+
+struct Trial {
+    blackframe_threshold: &'static str,
+    threshold: f32,
+    fit: Fit,
+}
+
+/// Sweeps match thresholds, sample rate and offsets against labeled boundaries
+/// and prints the best parameters as config lines.
+/// `sample_rate` is used as is for blackframe metric which compares every frame.
+pub fn tune(
+    dir: &str,
+    labels_filepath: &str,
+    sides: &[TuneSide],
+    metric: MatchMetric,
+    sample_rate: &str,
+) {
+    if sides.is_empty() {
+        panic!("Tune requires --if and/or --of reference frame");
+    }
+    let content = fs::read_to_string(labels_filepath).expect("Unable to read labels file");
+    let labels = parse_labels(&content);
+    let blackframe_thresholds: &[&'static str] = match metric {
+        MatchMetric::Blackframe => &BLACKFRAME_THRESHOLDS,
+        _ => &[""],
+    };
+    let sample_rates: Vec<f32> = match metric {
+        MatchMetric::Blackframe => vec![sample_rate
+            .parse()
+            .expect("Unable to parse frame sample rate")],
+        _ => SAMPLE_RATES.to_vec(),
+    };
+    let decode_rate = sample_rates.iter().copied().fold(0.0, f32::max);
+
+    // scores[blackframe threshold][file][side]
+    let scores: Vec<Vec<FileScores>> = blackframe_thresholds
+        .iter()
+        .map(|blackframe_threshold| {
+            labels
+                .iter()
+                .map(|label| {
+                    println!(
+                        "Tune {} (blackframe threshold {blackframe_threshold}) =>",
+                        label.file
+                    );
+                    let input_filepath = Path::new(dir).join(&label.file);
+                    let references: Vec<FrameReference> = sides
+                        .iter()
+                        .map(|side| FrameReference {
+                            frame_filepath: side.frame_filepath,
+                            match_threshold: "",
+                            // every frame is reported, amount is swept afterwards
+                            blackframe_amount: "0",
+                            blackframe_threshold,
+                            greater_than_duration: side.greater_than_duration,
+                            less_than_duration: side.less_than_duration,
+                        })
+                        .collect();
+                    frame_scores(
+                        input_filepath.to_str().expect("Unable to get file path"),
+                        &references,
+                        metric,
+                        decode_rate,
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut best: Option<(f32, Vec<Trial>, Fit)> = None;
+    for &rate in &sample_rates {
+        let mut trials: Vec<Trial> = Vec::new();
+        for (side_index, side) in sides.iter().enumerate() {
+            let side_labels: Vec<Option<f32>> = labels
+                .iter()
+                .map(|label| label.boundary(side.side))
+                .collect();
+            let mut side_best: Option<Trial> = None;
+            for (run, &blackframe_threshold) in blackframe_thresholds.iter().enumerate() {
+                for &threshold in threshold_grid(metric) {
+                    let threshold_str = threshold.to_string();
+                    let reference = FrameReference {
+                        frame_filepath: side.frame_filepath,
+                        match_threshold: &threshold_str,
+                        blackframe_amount: &threshold_str,
+                        blackframe_threshold,
+                        greater_than_duration: side.greater_than_duration,
+                        less_than_duration: side.less_than_duration,
+                    };
+                    let boundaries: Vec<Option<f32>> = scores[run]
+                        .iter()
+                        .map(|file_scores| {
+                            let side_scores = match metric {
+                                MatchMetric::Blackframe => file_scores[side_index].clone(),
+                                _ => subsample(&file_scores[side_index], rate),
+                            };
+                            let intervals = score_intervals(&side_scores, &reference, metric, rate);
+                            let interval = match side.side {
+                                Side::Intro => intervals.last(),
+                                Side::Outro => intervals.first(),
+                            };
+                            interval.map(|interval| side.side.boundary(interval))
+                        })
+                        .collect();
+                    let fit = fit_offset(&boundaries, &side_labels);
+                    if side_best
+                        .as_ref()
+                        .is_none_or(|trial| fit.is_better(&trial.fit))
+                    {
+                        side_best = Some(Trial {
+                            blackframe_threshold,
+                            threshold,
+                            fit,
+                        });
+                    }
+                }
+            }
+            trials.push(side_best.expect("Threshold grid is empty"));
+        }
+
+        let total = Fit {
+            offset: 0.0,
+            mean_error: trials.iter().map(|trial| trial.fit.mean_error).sum::<f32>()
+                / trials.len() as f32,
+            misses: trials.iter().map(|trial| trial.fit.misses).sum(),
+            false_positives: trials.iter().map(|trial| trial.fit.false_positives).sum(),
+            errors: Vec::new(),
+        };
+        if best
+            .as_ref()
+            .is_none_or(|(_, _, best_total)| total.is_better(best_total))
+        {
+            best = Some((rate, trials, total));
+        }
+    }
+
+    let (rate, trials, total) = best.expect("Sample rates are empty");
+    println!(
+        "\nBest parameters: mean absolute error {:.2}s, {} missed, {} false (paste into {})",
+        total.mean_error, total.misses, total.false_positives, CONFIG_FILENAME
+    );
+    let metric_name = metric
+        .to_possible_value()
+        .expect("Match metric has a name")
+        .get_name()
+        .to_owned();
+    println!("match-metric = {metric_name}");
+    if metric != MatchMetric::Blackframe {
+        println!("sample-rate = {rate}");
+    }
+    for (side, trial) in sides.iter().zip(&trials) {
+        let (short, name) = match side.side {
+            Side::Intro => ("i", "intro"),
+            Side::Outro => ("o", "outro"),
+        };
+        match metric {
+            MatchMetric::Blackframe => {
+                println!("{short}fba = {}", trial.threshold);
+                println!("{short}fbt = {}", trial.blackframe_threshold);
+            }
+            _ => println!("{short}fmt = {}", trial.threshold),
+        }
+        println!("{name}-offset = {}", trial.fit.offset);
+    }
+
+    println!("\nErrors per file:");
+    for (index, label) in labels.iter().enumerate() {
+        let errors: Vec<String> = sides
+            .iter()
+            .zip(&trials)
+            .map(|(side, trial)| {
+                let name = match side.side {
+                    Side::Intro => "intro",
+                    Side::Outro => "outro",
+                };
+                match (label.boundary(side.side), trial.fit.errors[index]) {
+                    (None, _) => format!("{name} -"),
+                    (Some(_), None) => format!("{name} missed"),
+                    (Some(_), Some(error)) => format!("{name} {error:.2}s"),
+                }
+            })
+            .collect();
+        println!("{}: {}", label.file, errors.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit_offset, median, parse_labels, subsample, Label};
+
+    #[test]
+    fn parse_labels_with_header() {
+        let labels = parse_labels("file,intro_end,outro_start\ne01.mkv,1:30,1290\ne02.mkv,,\n");
+
+        assert_eq!(
+            labels,
+            vec![
+                Label {
+                    file: "e01.mkv".to_owned(),
+                    intro_end: Some(90.0),
+                    outro_start: Some(1290.0)
+                },
+                Label {
+                    file: "e02.mkv".to_owned(),
+                    intro_end: None,
+                    outro_start: None
+                }
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unable to parse label line e02.mkv,x,")]
+    fn parse_labels_panic_time() {
+        parse_labels("e01.mkv,90,\ne02.mkv,x,");
+    }
+
+    #[test]
+    fn subsample_keeps_first_of_period() {
+        let scores: Vec<(f32, f32)> = (0..8).map(|i| (i as f32 * 0.25, i as f32)).collect();
+
+        assert_eq!(subsample(&scores, 1.0), vec![(0.0, 0.0), (1.0, 4.0)]);
+        assert_eq!(subsample(&scores, 4.0).len(), 8);
+    }
+
+    #[test]
+    fn median_of_values() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(median(&[3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&[4.0, 1.0, 2.0, 3.0]), Some(2.5));
+    }
+
+    #[test]
+    fn fit_offset_with_misses() {
+        let fit = fit_offset(
+            &[Some(10.0), Some(20.0), None, Some(5.0)],
+            &[Some(10.5), Some(20.5), Some(30.0), None],
+        );
+
+        assert_eq!(fit.offset, 0.5);
+        assert_eq!(fit.mean_error, 0.0);
+        assert_eq!(fit.misses, 1);
+        assert_eq!(fit.false_positives, 1);
+        assert_eq!(fit.errors, vec![Some(0.0), Some(0.0), None, None]);
+    }
+
+    #[test]
+    fn fit_is_better_with_fewer_false_positives() {
+        let precise = fit_offset(&[Some(10.0), Some(5.0)], &[Some(10.0), None]);
+        let silent = fit_offset(&[Some(10.5), None], &[Some(10.0), None]);
+
+        assert_eq!(precise.false_positives, 1);
+        assert!(silent.is_better(&precise));
+        assert!(!precise.is_better(&silent));
+    }
+}